@@ -0,0 +1,120 @@
+#ifndef PLEROMAKENOMA_H
+#define PLEROMAKENOMA_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define PK_API_VERSION 2
+
+#define PK_OK 0
+
+#define PK_ERR_NULL -1
+
+#define PK_ERR_GAME_OVER -2
+
+#define PK_ERR_NO_SUCH_SLOT -3
+
+#define PK_ERR_NO_SUCH_WORLD -4
+
+#define PK_ERR_BAD_KIND -5
+
+#define PK_ERR_BAD_GAME -6
+
+#define PK_MOVE_PUSH 0
+
+#define PK_MOVE_CLAIM 1
+
+#define PK_WORLDS 4
+
+#define PK_HAND_SIZE 4
+
+#define PK_MOVE_COUNT 20
+
+typedef struct PkGame PkGame;
+
+typedef struct PkRules {
+  uint16_t deck_size;
+  int8_t clamp;
+  int8_t max_card;
+  const int8_t *starting_offsets;
+  uintptr_t offset_count;
+} PkRules;
+
+typedef struct PkMove {
+  uint8_t kind;
+  uint8_t slot;
+  uint8_t world;
+} PkMove;
+
+typedef struct PkState {
+  int8_t polarity[PK_WORLDS];
+  int8_t hands[2][PK_HAND_SIZE];
+  bool pleroma;
+  uint16_t score;
+  uint16_t turn;
+  uint16_t cards_left;
+  bool over;
+} PkState;
+
+typedef struct PkOutcome {
+  int8_t card;
+  uint8_t balanced_count;
+  uint8_t balanced[PK_WORLDS];
+  bool clamped;
+  bool drew;
+  int8_t drawn;
+} PkOutcome;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Deals a game with custom rules, or returns null if the rules cannot deal one: the clamp
+// and max_card must be 1 to 6, and every starting offset within the clamp.
+//
+// # Safety
+// `rules` must be null or point to a PkRules whose `starting_offsets` holds `offset_count` values.
+PkGame *pk_game_new(const PkRules *rules, uint32_t seed);
+
+// Deals a game under a preset: 0 Classic, 1 Quick, 2 Marathon, 3 Tight. Null for any other.
+PkGame *pk_game_new_preset(uint32_t preset, uint32_t seed);
+
+// # Safety
+// `game` must be null or come from pk_game_new or pk_game_new_preset and not be freed already.
+void pk_game_free(PkGame *game);
+
+// Writes up to `capacity` legal moves to `out` and returns how many there are in total,
+// which is at most PK_MOVE_COUNT. Pass a null `out` to only count them.
+//
+// # Safety
+// `game` must be null or a live game, `out` null or room for `capacity` moves.
+uintptr_t pk_game_legal_moves(const PkGame *game, PkMove *out, uintptr_t capacity);
+
+// Plays a move and, if `outcome` is not null, describes what it did. Returns PK_OK or one of
+// the PK_ERR codes, in which case the game is unchanged.
+//
+// # Safety
+// `game` must be null or a live game, `outcome` null or valid for writing one PkOutcome.
+int32_t pk_game_apply(PkGame *game, PkMove mv, PkOutcome *outcome);
+
+// Copies the visible state of the game into `out`. The order of the deck stays hidden.
+//
+// # Safety
+// `game` must be null or a live game, `out` null or valid for writing one PkState.
+int32_t pk_game_state(const PkGame *game, PkState *out);
+
+// A static, nul-terminated description of a status code. Never free it.
+const char *pk_error_message(int32_t code);
+
+uint32_t pk_api_version(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PLEROMAKENOMA_H */
@@ -0,0 +1,236 @@
+use std::{collections::VecDeque, time::Duration};
+use bevy::{prelude::*, render::view::RenderLayers};
+use bevy_tweening::{lens::TransformPositionLens, EaseFunction};
+use serde::{Deserialize, Serialize};
+use pleromakenoma::rules::{Move, WORLDS};
+use crate::{date, move_entity, session::{Session, Turn}, storage, GameSettings};
+
+const ACHIEVEMENTS_FILE: &str = "achievements.json";
+const TOAST_LAYER: u8 = 2;
+const TOAST_SHOWN: f32 = 3.; // seconds on screen, slides excluded
+const TOAST_SLIDE: Duration = Duration::from_millis(400);
+const TOAST_IN: Vec3 = Vec3::new(400., 270., 10.);
+const TOAST_OUT: Vec3 = Vec3::new(800., 270., 10.);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Achievement{
+    FullClaim,
+    NoClamps,
+    PleromaOnly,
+    TenWorlds,
+}
+
+impl Achievement{
+    pub const ALL: [Achievement; 4] = [Achievement::FullClaim, Achievement::NoClamps, Achievement::PleromaOnly, Achievement::TenWorlds];
+
+    pub fn name(self) -> &'static str{
+        match self{
+            Achievement::FullClaim => "Fourfold Balance",
+            Achievement::NoClamps => "Within Bounds",
+            Achievement::PleromaOnly => "Light Bearer",
+            Achievement::TenWorlds => "Demiurge",
+        }
+    }
+
+    pub fn description(self) -> &'static str{
+        match self{
+            Achievement::FullClaim => "Balance all four worlds in one claim",
+            Achievement::NoClamps => "Finish a game without pushing a world off the board",
+            Achievement::PleromaOnly => "Score a game balancing only in Pleroma",
+            Achievement::TenWorlds => "Balance 10 worlds in one game",
+        }
+    }
+
+    // Checked after every turn.
+    fn unlocked_by_turn(self, turn: &Turn) -> bool{
+        match self{
+            Achievement::FullClaim => matches!(turn.mv, Move::Claim{ .. }) && turn.outcome.balanced.len() == WORLDS,
+            _ => false,
+        }
+    }
+
+    // Checked once the last card is drawn.
+    fn unlocked_by_game(self, history: &[Turn], score: u16) -> bool{
+        match self{
+            Achievement::NoClamps => !history.iter().any(|turn| turn.outcome.clamped),
+            Achievement::PleromaOnly => score > 0 && history.iter().all(|turn| turn.pleroma || turn.outcome.balanced.is_empty()),
+            Achievement::TenWorlds => score >= 10,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Unlock{
+    pub achievement: Achievement,
+    pub date: u64, // unix seconds
+}
+
+#[derive(Resource, Default, Serialize, Deserialize)]
+pub struct Achievements{
+    pub unlocked: Vec<Unlock>,
+    #[serde(skip)]
+    profile: String, // see storage.rs
+    #[serde(skip)]
+    toasts: VecDeque<Achievement>, // unlocked but not yet announced
+    #[serde(skip)]
+    checked_turns: usize, // how much of the session history has been looked at
+}
+
+impl Achievements{
+    pub fn load(profile: &str) -> Self{
+        let achievements: Achievements = storage::load_profile(profile, ACHIEVEMENTS_FILE).unwrap_or_default();
+        Achievements{ profile: profile.to_owned(), ..achievements }
+    }
+
+    pub fn is_unlocked(&self, achievement: Achievement) -> bool{
+        self.unlocked.iter().any(|unlock| unlock.achievement == achievement)
+    }
+
+    fn unlock(&mut self, achievement: Achievement){
+        if self.is_unlocked(achievement){
+            return;
+        }
+        self.unlocked.push(Unlock{ achievement, date: date::now() });
+        self.toasts.push_back(achievement);
+        storage::save(&storage::profile_file(&self.profile, ACHIEVEMENTS_FILE), self);
+    }
+}
+
+pub fn check_achievements(session: Res<Session>, mut achievements: ResMut<Achievements>){
+    if session.game.mode.is_versus() || !session.scored{ // every achievement is about a solitaire game played here
+        return;
+    }
+    if achievements.checked_turns > session.history.len(){ // a new game was dealt
+        achievements.checked_turns = 0;
+    }
+    while achievements.checked_turns < session.history.len(){
+        let turn = &session.history[achievements.checked_turns];
+        achievements.checked_turns += 1;
+        for achievement in Achievement::ALL{
+            if achievement.unlocked_by_turn(turn){
+                achievements.unlock(achievement);
+            }
+        }
+        if achievements.checked_turns == session.history.len() && session.game.is_over(){
+            for achievement in Achievement::ALL{
+                if achievement.unlocked_by_game(&session.history, session.game.score){
+                    achievements.unlock(achievement);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Toast{
+    age: f32,
+    leaving: bool,
+}
+
+pub fn spawn_toast_camera(mut commands: Commands){
+    commands.spawn(( // above the menus, so a toast earned on the last turn survives the game over screen
+        Camera2dBundle {
+            camera: Camera{
+                order: 3,
+                ..default()
+            },
+            camera_2d: Camera2d{
+                clear_color: bevy::core_pipeline::clear_color::ClearColorConfig::None,
+            },
+            ..default()
+        },
+        UiCameraConfig{ show_ui: false },
+        RenderLayers::layer(TOAST_LAYER),
+    ));
+}
+
+pub fn show_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<GameSettings>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut achievements: ResMut<Achievements>,
+    mut query: Query<(Entity, &mut Toast, &Transform)>,
+){
+    let slide = if settings.instant_animations { 0. } else { TOAST_SLIDE.as_secs_f32() / settings.animation_speed };
+    let mut showing = false;
+    for (entity, mut toast, trans) in query.iter_mut(){
+        showing = true;
+        toast.age += time.delta_seconds();
+        if toast.leaving{
+            if toast.age > slide{
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        else if toast.age > slide + TOAST_SHOWN{
+            toast.age = 0.;
+            toast.leaving = true;
+            let lens = TransformPositionLens{ start: trans.translation, end: TOAST_OUT };
+            move_entity(&mut commands, &settings, entity, EaseFunction::QuadraticIn, TOAST_SLIDE, lens, None);
+        }
+    }
+    if showing{
+        return;
+    }
+    let Some(achievement) = achievements.toasts.pop_front() else { return };
+    let texture_atlas = TextureAtlas::from_grid(
+        asset_server.load("spritesheet.png"),
+        Vec2::new(16.0, 16.0),
+        80, 2, None, None
+    );
+    let texture_atlas_handle = texture_atlases.add(texture_atlas);
+    let font = asset_server.load("Play-Regular.ttf");
+    let layer = RenderLayers::layer(TOAST_LAYER);
+    let toast = commands.spawn((SpriteBundle {
+        sprite: Sprite {
+            color: Color::rgba(0.1, 0.1, 0.1, 0.9),
+            custom_size: Some(Vec2::new(320., 64.)),
+            ..default()
+        },
+        transform: Transform::from_translation(TOAST_OUT),
+        ..default()
+    },
+    Toast{ age: 0., leaving: false },
+    layer,
+    )).with_children(|parent| {
+        parent.spawn((SpriteSheetBundle {
+            texture_atlas: texture_atlas_handle,
+            sprite: TextureAtlasSprite{
+                index : 8,
+                custom_size: Some(Vec2::new(32.0, 32.0)),
+                ..default()
+            },
+            transform: Transform {
+                translation: Vec3::new(-128., 0., 1.),
+                rotation: Quat::from_rotation_z(std::f32::consts::PI/4.0),
+                ..default()
+            },
+            ..default()
+        },
+        layer,
+        ));
+        parent.spawn((Text2dBundle {
+            text: Text::from_sections([
+                TextSection::new(format!("{}\n", achievement.name()), TextStyle {
+                    font: font.clone(),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                }),
+                TextSection::new(achievement.description(), TextStyle {
+                    font: font.clone(),
+                    font_size: 12.0,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                }),
+            ]).with_alignment(TextAlignment::Left),
+            text_anchor: bevy::sprite::Anchor::CenterLeft,
+            transform: Transform::from_xyz(-96., 0., 1.),
+            ..default()
+        },
+        layer,
+        ));
+    }).id();
+    let lens = TransformPositionLens{ start: TOAST_OUT, end: TOAST_IN };
+    move_entity(&mut commands, &settings, toast, EaseFunction::QuadraticOut, TOAST_SLIDE, lens, None);
+}
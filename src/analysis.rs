@@ -0,0 +1,151 @@
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+use bevy::{prelude::*, tasks::{AsyncComputeTaskPool, Task, futures_lite::future}};
+use pleromakenoma::{notation::Notation, rules::{Game, Move}, solver::{self, TurnReview}};
+use crate::{menu::{spawn_menu, MenuActivated, MenuButton, MenuCursor, MenuScreen}, session::Session};
+
+const MISTAKES_SHOWN: usize = 3;
+
+// The finished game replayed against the solver, one turn at a time.
+#[derive(Resource, Default)]
+pub struct Analysis{
+    task: Option<Task<Vec<TurnReview>>>, // the solver takes a few seconds on long decks
+    cancel: Arc<AtomicBool>, // for the task, which does not stop just by being dropped
+    reviews: Vec<TurnReview>,
+    turn: usize,
+}
+
+fn start(session: &Session) -> Game{
+    Game::new(session.preset.rules(), session.seed)
+}
+
+fn describe(game: &Game, mv: Move) -> String{
+    let mut after = game.clone();
+    match after.apply(mv){
+        Ok(outcome) => Notation::new(game.pleroma, mv, &outcome, &after).to_string(),
+        Err(err) => err.to_string(),
+    }
+}
+
+fn analysis_lines(session: &Session, analysis: &Analysis) -> Vec<String>{
+    if analysis.task.is_some(){
+        return vec!["The solver is going over your game...".to_owned(), String::new()];
+    }
+    let Some(review) = analysis.reviews.get(analysis.turn) else {
+        return vec!["There is nothing to analyse".to_owned(), String::new()];
+    };
+    let mut game = start(session);
+    for turn in session.history.iter().take(analysis.turn){
+        game.apply(turn.mv).expect("the history was played on this deal");
+    }
+    let numbers = |values: &[i8]| values.iter().map(|value| format!("{value:+}")).collect::<Vec<_>>().join(" ");
+    let mut lines = vec![
+        format!("Turn {} of {}", analysis.turn + 1, analysis.reviews.len()),
+        format!("{}  worlds {}  hand {}", if game.pleroma { "Pleroma" } else { "Kenoma" }, numbers(&game.polarity), numbers(game.hand())),
+        format!("Played  {}", describe(&game, review.played)),
+    ];
+    if review.lost == 0{
+        lines.push("Best play".to_owned());
+    }
+    else {
+        lines.push(format!("Solver  {}", describe(&game, review.best)));
+        lines.push(format!("{} balanced world{} lost", review.lost, if review.lost == 1 { "" } else { "s" }));
+    }
+    lines.push(String::new());
+    let total: u16 = analysis.reviews.iter().map(|review| review.lost).sum();
+    lines.push(format!("{} of {} possible worlds balanced", session.game.score, session.game.score + total));
+    for (turn, review) in biggest_mistakes(&analysis.reviews){
+        lines.push(format!("Turn {}: -{}", turn + 1, review.lost));
+    }
+    lines.push(String::new());
+    lines
+}
+
+fn biggest_mistakes(reviews: &[TurnReview]) -> Vec<(usize, TurnReview)>{
+    let mut mistakes: Vec<(usize, TurnReview)> = reviews.iter().copied().enumerate().filter(|(_, review)| review.lost > 0).collect();
+    mistakes.sort_by(|(a_turn, a), (b_turn, b)| b.lost.cmp(&a.lost).then(a_turn.cmp(b_turn)));
+    mistakes.truncate(MISTAKES_SHOWN);
+    mistakes
+}
+
+fn analysis_items(analysis: &Analysis) -> Vec<(MenuButton, String, bool)>{
+    let ready = analysis.task.is_none() && !analysis.reviews.is_empty();
+    vec![
+        (MenuButton::PreviousTurn, "Previous turn".to_owned(), ready && analysis.turn > 0),
+        (MenuButton::NextTurn, "Next turn".to_owned(), ready && analysis.turn + 1 < analysis.reviews.len()),
+        (MenuButton::WorstMistake, "Next big mistake".to_owned(), ready && !biggest_mistakes(&analysis.reviews).is_empty()),
+        (MenuButton::Back, "Back".to_owned(), true),
+    ]
+}
+
+pub fn spawn_analysis(mut commands: Commands, asset_server: Res<AssetServer>, session: Res<Session>, mut analysis: ResMut<Analysis>, mut cursor: ResMut<MenuCursor>){
+    let (game, moves): (Game, Vec<Move>) = (start(&session), session.history.iter().map(|turn| turn.mv).collect());
+    let cancel = Arc::new(AtomicBool::new(false));
+    analysis.cancel = cancel.clone();
+    analysis.task = Some(AsyncComputeTaskPool::get().spawn(async move { solver::review(&game, &moves, &cancel) }));
+    analysis.reviews.clear();
+    analysis.turn = 0;
+    cursor.index = 3;
+    spawn_menu(&mut commands, &asset_server, "Analysis", analysis_lines(&session, &analysis), analysis_items(&analysis), Color::rgba(0., 0., 0., 0.9));
+}
+
+pub fn step_analysis(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    mut events: EventReader<MenuActivated>,
+    asset_server: Res<AssetServer>,
+    session: Res<Session>,
+    mut analysis: ResMut<Analysis>,
+    mut cursor: ResMut<MenuCursor>,
+    query_screen: Query<Entity, With<MenuScreen>>,
+){
+    let mut changed = false;
+    if let Some(task) = analysis.task.as_mut(){
+        if let Some(reviews) = future::block_on(future::poll_once(task)){
+            analysis.reviews = reviews;
+            analysis.task = None;
+            cursor.index = 1;
+            changed = true;
+        }
+    }
+    let last = analysis.reviews.len().saturating_sub(1);
+    let mut step = |analysis: &mut Analysis, turn: usize|{
+        if analysis.task.is_none() && turn <= last && turn != analysis.turn{
+            analysis.turn = turn;
+            changed = true;
+        }
+    };
+    if keys.just_pressed(KeyCode::Left){
+        let turn = analysis.turn.saturating_sub(1);
+        step(&mut analysis, turn);
+    }
+    if keys.just_pressed(KeyCode::Right){
+        let turn = analysis.turn + 1;
+        step(&mut analysis, turn);
+    }
+    for MenuActivated(button) in events.iter(){
+        let turn = match button{
+            MenuButton::PreviousTurn => analysis.turn.saturating_sub(1),
+            MenuButton::NextTurn => analysis.turn + 1,
+            MenuButton::WorstMistake => { // cycles through the worst turns, worst first
+                let mistakes = biggest_mistakes(&analysis.reviews);
+                let next = mistakes.iter().position(|(turn, _)| *turn == analysis.turn).map_or(0, |index| (index + 1) % mistakes.len());
+                mistakes.get(next).map_or(analysis.turn, |(turn, _)| *turn)
+            }
+            _ => continue,
+        };
+        step(&mut analysis, turn);
+    }
+    if !changed{
+        return;
+    }
+    for entity in query_screen.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_menu(&mut commands, &asset_server, "Analysis", analysis_lines(&session, &analysis), analysis_items(&analysis), Color::rgba(0., 0., 0., 0.9));
+}
+
+// The review has no await points, so dropping its task would leave it running to the end.
+pub fn cancel_analysis(mut analysis: ResMut<Analysis>){
+    analysis.cancel.store(true, Ordering::Relaxed);
+    analysis.task = None;
+}
@@ -0,0 +1,61 @@
+// Generates a pack of puzzles with one solution each and prints it as JSON, in the format of
+// assets/puzzles.pack.json, which it can replace or be merged into.
+//
+//   cargo run --release --bin generate -- --count 10 --difficulty hard --first-seed 100 --name "Hard Ten"
+//
+// Each puzzle comes from its own seed, so a pack can be regenerated or extended later. Puzzles
+// whose seed only found several solutions are reported and left out.
+
+use std::process::exit;
+use pleromakenoma::{generator::{generate, Difficulty}, notation::Notation, puzzle::PuzzlePack};
+
+fn usage() -> !{
+    let names: Vec<_> = Difficulty::ALL.iter().map(|difficulty| difficulty.name().to_lowercase()).collect();
+    eprintln!("usage: generate [--count N] [--difficulty {}] [--first-seed N] [--name NAME]", names.join("|"));
+    exit(2);
+}
+
+fn parse<T: std::str::FromStr>(value: Option<String>) -> T{
+    value.and_then(|value| value.parse().ok()).unwrap_or_else(|| usage())
+}
+
+fn main(){
+    let mut count = 10_u64;
+    let mut difficulty = Difficulty::default();
+    let mut first_seed = 0_u64;
+    let mut name = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next(){
+        match arg.as_str(){
+            "--count" => count = parse(args.next()),
+            "--difficulty" => difficulty = args.next().and_then(|name| Difficulty::from_name(&name)).unwrap_or_else(|| usage()),
+            "--first-seed" => first_seed = parse(args.next()),
+            "--name" => name = Some(args.next().unwrap_or_else(|| usage())),
+            _ => usage(),
+        }
+    }
+    let mut pack = PuzzlePack{
+        name: name.unwrap_or_else(|| format!("Generated {}", difficulty.name())),
+        puzzles: Vec::new(),
+    };
+    for seed in first_seed..first_seed.saturating_add(count){
+        let Some((puzzle, rating)) = generate(difficulty, seed) else {
+            eprintln!("seed {seed}: nothing at this difficulty");
+            continue;
+        };
+        if rating.solutions > 1{
+            eprintln!("seed {seed}: {} solutions at best, skipped", rating.solutions);
+            continue;
+        }
+        // The solution goes to stderr only, so the pack itself gives nothing away.
+        let mut game = puzzle.game();
+        let line: Vec<String> = rating.solution.iter().map(|mv| {
+            let pleroma = game.pleroma;
+            let outcome = game.apply(*mv).expect("the solution was played on this puzzle");
+            Notation::new(pleroma, *mv, &outcome, &game).to_string()
+        }).collect();
+        eprintln!("seed {seed}: {}, branching {:.1}: {}", puzzle.goal.describe(), rating.branching, line.join(", "));
+        pack.puzzles.push(puzzle);
+    }
+    println!("{}", serde_json::to_string_pretty(&pack).expect("packs always serialize"));
+}
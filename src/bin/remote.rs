@@ -0,0 +1,21 @@
+// Plays the game through the JSON-lines protocol in src/protocol.rs, reading commands on
+// stdin and answering on stdout, until stdin closes.
+//
+//   echo '{"cmd":"new","seed":7}' | cargo run --bin remote
+
+use std::io::{self, BufRead, Write};
+use pleromakenoma::protocol::Remote;
+
+fn main() -> io::Result<()>{
+    let mut remote = Remote::new();
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines(){
+        let line = line?;
+        if line.trim().is_empty(){
+            continue;
+        }
+        writeln!(stdout, "{}", remote.handle_line(&line))?;
+        stdout.flush()?; // the other end waits for each reply before sending more
+    }
+    Ok(())
+}
@@ -0,0 +1,203 @@
+// Plays many seeded games headlessly and reports how the rules behave, for tuning presets.
+//
+//   cargo run --release --bin simulate -- --games 500 --preset tight --bot greedy --threads 8
+//
+// Any of --deck-size, --clamp, --max-card and --offsets (comma separated) override the preset.
+// --iterations and --time-ms set the search budget of the mcts bot, whichever runs out first.
+
+use std::{process::exit, sync::atomic::{AtomicU32, Ordering}, thread, time::{Duration, Instant}};
+use pleromakenoma::{bot::{Bot, GreedyBot, OracleBot, RandomBot}, mcts::{Budget, Mcts}, rules::{Game, Move, RulePreset, Rules}};
+
+const BOTS: [&str; 4] = ["random", "greedy", "mcts", "oracle"];
+const HISTOGRAM_WIDTH: usize = 40;
+
+struct Options{
+    games: u32,
+    first_seed: u32,
+    threads: u32,
+    rules: Rules,
+    preset: RulePreset,
+    bot: String,
+    budget: Budget,
+}
+
+// What one game did, gathered while it is played.
+#[derive(Default)]
+struct GameStats{
+    seed: u32,
+    score: u16,
+    turns: u16,
+    claims: u16,
+    pushes: u16,
+    clamps: u16,
+    off_balance: u8, // worlds still off 0 once the deck ran out
+    unbalanceable: u8, // of those, the ones no card left in hand could have brought back to 0
+}
+
+fn usage() -> !{
+    eprintln!("usage: simulate [--games N] [--first-seed N] [--threads N] [--preset NAME] [--bot {}]", BOTS.join("|"));
+    eprintln!("                [--iterations N] [--time-ms N] [--deck-size N] [--clamp N] [--max-card N] [--offsets A,B,..]");
+    exit(2);
+}
+
+fn parse<T: std::str::FromStr>(value: Option<String>) -> T{
+    value.and_then(|value| value.parse().ok()).unwrap_or_else(|| usage())
+}
+
+fn parse_options() -> Options{
+    let mut options = Options{
+        games: 100,
+        first_seed: 0,
+        threads: 1,
+        rules: RulePreset::Classic.rules(),
+        preset: RulePreset::Classic,
+        bot: "greedy".to_owned(),
+        budget: Budget::iterations(1000),
+    };
+    let mut overrides = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next(){
+        match arg.as_str(){
+            "--games" => options.games = parse(args.next()),
+            "--first-seed" => options.first_seed = parse(args.next()),
+            "--threads" => options.threads = parse::<u32>(args.next()).max(1),
+            "--preset" => {
+                options.preset = args.next().and_then(|name| RulePreset::from_name(&name)).unwrap_or_else(|| usage());
+                options.rules = options.preset.rules();
+            }
+            "--bot" => {
+                options.bot = args.next().filter(|name| BOTS.contains(&name.as_str())).unwrap_or_else(|| usage());
+            }
+            "--iterations" => options.budget.iterations = parse(args.next()),
+            "--time-ms" => options.budget.time = Some(Duration::from_millis(parse(args.next()))),
+            "--deck-size" | "--clamp" | "--max-card" | "--offsets" => overrides.push((arg, args.next())),
+            _ => usage(),
+        }
+    }
+    // Applied last so that they win over --preset wherever it was given.
+    for (arg, value) in overrides{
+        match arg.as_str(){
+            "--deck-size" => options.rules.deck_size = parse(value),
+            "--clamp" => options.rules.clamp = parse(value),
+            "--max-card" => options.rules.max_card = parse(value),
+            _ => {
+                let offsets = value.unwrap_or_else(|| usage());
+                options.rules.starting_offsets = offsets.split(',').map(|offset| parse(Some(offset.trim().to_owned()))).collect();
+            }
+        }
+    }
+    if !options.rules.is_valid(){
+        eprintln!("the clamp and the highest card must be 1 to 6, and every starting offset within the clamp");
+        exit(2);
+    }
+    options
+}
+
+fn make_bot(options: &Options, seed: u32) -> Box<dyn Bot>{
+    match options.bot.as_str(){
+        "random" => Box::new(RandomBot::new(seed as u64)),
+        "mcts" => Box::new(Mcts::new(options.budget, seed as u64)),
+        "oracle" => Box::new(OracleBot::new()),
+        _ => Box::new(GreedyBot::new(seed as u64)),
+    }
+}
+
+fn simulate(options: &Options, seed: u32) -> GameStats{
+    let mut bot = make_bot(options, seed);
+    let mut game = Game::new(options.rules.clone(), seed);
+    let mut stats = GameStats{ seed, ..GameStats::default() };
+    while !game.is_over(){
+        let mv = bot.choose(&game);
+        let outcome = game.apply(mv).expect("bots only pick legal moves");
+        match mv{
+            Move::Push{ .. } => {
+                stats.pushes += 1;
+                stats.clamps += outcome.clamped as u16;
+            }
+            Move::Claim{ .. } => stats.claims += 1,
+        }
+    }
+    stats.score = game.score;
+    stats.turns = game.turn;
+    for polarity in game.polarity.into_iter().filter(|polarity| *polarity != 0){
+        stats.off_balance += 1;
+        // Kenoma balances a positive world with a card of the same value, Pleroma a negative one.
+        let hand = game.hands[(polarity < 0) as usize];
+        stats.unbalanceable += !hand.contains(&polarity.abs()) as u8;
+    }
+    stats
+}
+
+fn run(options: &Options) -> Vec<GameStats>{
+    let next = AtomicU32::new(0);
+    let mut results: Vec<GameStats> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..options.threads).map(|_| scope.spawn(|| {
+            let mut done = Vec::new();
+            loop{
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= options.games{
+                    break done;
+                }
+                done.push(simulate(options, options.first_seed.wrapping_add(index)));
+            }
+        })).collect();
+        workers.into_iter().flat_map(|worker| worker.join().expect("a simulation thread panicked")).collect()
+    });
+    results.sort_by_key(|stats| stats.seed);
+    results
+}
+
+fn mean(values: impl Iterator<Item = f64>, count: usize) -> f64{
+    values.sum::<f64>() / count.max(1) as f64
+}
+
+fn report(options: &Options, results: &[GameStats], elapsed: Duration){
+    let games = results.len();
+    let rules = &options.rules;
+    let bot_name = make_bot(options, 0).name();
+    println!("{} rules (deck {}, clamp {}, cards 1-{}, offsets {:?}), {bot_name}, {games} games from seed {}",
+        if *rules == options.preset.rules() { options.preset.name() } else { "Custom" },
+        rules.deck_size, rules.clamp, rules.max_card, rules.starting_offsets, options.first_seed);
+    if games == 0{
+        return;
+    }
+
+    let mut scores: Vec<u16> = results.iter().map(|stats| stats.score).collect();
+    scores.sort_unstable();
+    let score_mean = mean(scores.iter().map(|score| *score as f64), games);
+    let deviation = mean(scores.iter().map(|score| (*score as f64 - score_mean).powi(2)), games).sqrt();
+    println!();
+    println!("score     mean {score_mean:.2}  sd {deviation:.2}  min {}  median {}  max {}",
+        scores[0], scores[games / 2], scores[games - 1]);
+    let highest = scores[games - 1] as usize;
+    let mut histogram = vec![0_usize; highest + 1];
+    for score in scores.iter(){
+        histogram[*score as usize] += 1;
+    }
+    let tallest = *histogram.iter().max().unwrap_or(&1);
+    for (score, count) in histogram.iter().enumerate().skip(scores[0] as usize){
+        let bar = "#".repeat((count * HISTOGRAM_WIDTH).div_ceil(tallest));
+        println!("  {score:>3} | {bar:<HISTOGRAM_WIDTH$} {count} ({:.1}%)", *count as f64 * 100. / games as f64);
+    }
+
+    let pushes: u32 = results.iter().map(|stats| stats.pushes as u32).sum();
+    let clamps: u32 = results.iter().map(|stats| stats.clamps as u32).sum();
+    let stranded = results.iter().filter(|stats| stats.unbalanceable > 0).count();
+    println!();
+    println!("turns     mean {:.2}, of which {:.2} claims", mean(results.iter().map(|stats| stats.turns as f64), games),
+        mean(results.iter().map(|stats| stats.claims as f64), games));
+    println!("clamps    {:.1}% of pushes, {:.2} per game", clamps as f64 * 100. / pushes.max(1) as f64, clamps as f64 / games as f64);
+    println!("end       {:.2} worlds off balance, {:.2} of them beyond the last hand",
+        mean(results.iter().map(|stats| stats.off_balance as f64), games),
+        mean(results.iter().map(|stats| stats.unbalanceable as f64), games));
+    println!("          {:.1}% of decks end with a world the last hand cannot balance", stranded as f64 * 100. / games as f64);
+    println!();
+    println!("took {:.2} s on {} threads", elapsed.as_secs_f64(), options.threads);
+}
+
+fn main(){
+    let options = parse_options();
+    let started = Instant::now();
+    let results = run(&options);
+    report(&options, &results, started.elapsed());
+}
@@ -0,0 +1,322 @@
+// Plays every entrant on the same seeds and ranks them, with head-to-head results per seed.
+//
+//   cargo run --release --bin tournament -- --games 100 --bot greedy --bot mcts:2000 \
+//       --process "mine=python3 my_bot.py"
+//
+// Built-in bots are random, greedy, oracle and mcts, the last optionally with ":iterations".
+// A process entrant runs the command given after its name and plays through the protocol of
+// src/protocol.rs with the roles reversed: the tournament sends it the reply to a new game,
+// then answers each command it writes until the game is over, and deals the next one. Only
+// state, play, claim and move are allowed. A process that exits, keeps sending bad commands or
+// takes longer than --move-timeout-ms (5000 by default) over a move forfeits its remaining games
+// on the score it had.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{exit, Child, ChildStdin, Command as Process, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+use pleromakenoma::{
+    bot::{Bot, GreedyBot, OracleBot, RandomBot},
+    mcts::{Budget, Mcts},
+    protocol::{Command, ErrorCode, ProtocolError, Remote, Reply},
+    rules::{Game, RulePreset},
+};
+
+const MAX_BAD_COMMANDS: u32 = 100; // per game, before a process forfeits it
+const MOVE_TIMEOUT: Duration = Duration::from_secs(5);
+const Z_95: f64 = 1.96;
+
+enum Kind{
+    Builtin(String),
+    Process(Vec<String>),
+}
+
+struct Entrant{
+    name: String,
+    kind: Kind,
+}
+
+struct Results{
+    scores: Vec<u16>, // one per seed, in seed order
+    problem: Option<String>,
+}
+
+fn usage() -> !{
+    eprintln!("usage: tournament [--games N] [--first-seed N] [--preset NAME] [--move-timeout-ms N] (--bot NAME[:ITERATIONS] | --process NAME=COMMAND)...");
+    exit(2);
+}
+
+fn builtin(spec: &str, seed: u32) -> Option<Box<dyn Bot>>{
+    let seed = seed as u64;
+    let (name, iterations) = match spec.split_once(':'){
+        Some((name, iterations)) => (name, Some(iterations.parse().ok()?)),
+        None => (spec, None),
+    };
+    Some(match (name, iterations){
+        ("random", None) => Box::new(RandomBot::new(seed)),
+        ("greedy", None) => Box::new(GreedyBot::new(seed)),
+        ("oracle", None) => Box::new(OracleBot::new()),
+        ("mcts", iterations) => Box::new(Mcts::new(Budget::iterations(iterations.unwrap_or(1000)), seed)),
+        _ => return None,
+    })
+}
+
+struct External{
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>, // closes when the process stops writing
+    timeout: Duration, // to come up with each move
+}
+
+impl External{
+    fn spawn(command: &[String], timeout: Duration) -> Result<Self, String>{
+        let mut child = Process::new(&command[0]).args(&command[1..])
+            .stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()
+            .map_err(|err| format!("could not start {}: {err}", command[0]))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        // Read on a thread of its own, so that a process which hangs cannot hang the tournament.
+        // The thread ends once the process is killed and its stdout closes.
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in stdout.lines(){
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err(){
+                    break;
+                }
+            }
+        });
+        Ok(External{ child, stdin, lines, timeout })
+    }
+
+    fn send(&mut self, reply: &Reply) -> Result<(), String>{
+        let line = serde_json::to_string(reply).expect("replies always serialize");
+        writeln!(self.stdin, "{line}").and_then(|_| self.stdin.flush()).map_err(|_| "stopped reading".to_owned())
+    }
+
+    // Plays one game, returning the score along with what went wrong if it did not finish.
+    fn play(&mut self, preset: RulePreset, seed: u32) -> (u16, Option<String>){
+        let mut remote = Remote::new();
+        let dealt = remote.handle(Command::New{ preset: Some(preset.name().to_owned()), seed: Some(seed), rules: None });
+        let score = |remote: &Remote| remote.game().map_or(0, |game| game.score);
+        if let Err(err) = self.send(&dealt){
+            return (0, Some(err));
+        }
+        let mut bad_commands = 0;
+        let mut turn = 0;
+        let mut deadline = Instant::now() + self.timeout;
+        while let Some(game) = remote.game().filter(|game| !game.is_over()){
+            if game.turn != turn{ // state commands and bad ones do not buy more time
+                turn = game.turn;
+                deadline = Instant::now() + self.timeout;
+            }
+            let line = match self.lines.recv_timeout(deadline.saturating_duration_since(Instant::now())){
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => return (score(&remote), Some(format!("took longer than {} ms over a move", self.timeout.as_millis()))),
+                Err(RecvTimeoutError::Disconnected) => return (score(&remote), Some("exited".to_owned())),
+            };
+            if line.trim().is_empty(){
+                continue;
+            }
+            let reply = match serde_json::from_str::<Command>(&line){
+                Ok(Command::New{ .. } | Command::Undo) => Reply::error(ProtocolError::new(ErrorCode::NotAllowed, "the tournament deals the games and moves cannot be taken back")),
+                Ok(command) => remote.handle(command),
+                Err(err) => Reply::error(ProtocolError::new(ErrorCode::BadCommand, err)),
+            };
+            if let Some(err) = reply.error.as_ref(){
+                bad_commands += 1;
+                if bad_commands > MAX_BAD_COMMANDS{
+                    return (score(&remote), Some(format!("too many bad commands, the last: {err}")));
+                }
+            }
+            if let Err(err) = self.send(&reply){
+                return (score(&remote), Some(err));
+            }
+        }
+        (score(&remote), None)
+    }
+}
+
+impl Drop for External{
+    fn drop(&mut self){
+        // Nothing more is coming its way, so a process still waiting for a game is stopped.
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn run(entrant: &Entrant, preset: RulePreset, seeds: &[u32], timeout: Duration) -> Results{
+    match &entrant.kind{
+        Kind::Builtin(spec) => Results{
+            scores: seeds.iter().map(|seed| {
+                let mut bot = builtin(spec, *seed).expect("checked when parsing arguments");
+                let mut game = Game::new(preset.rules(), *seed);
+                while !game.is_over(){
+                    let mv = bot.choose(&game);
+                    game.apply(mv).expect("bots only pick legal moves");
+                }
+                game.score
+            }).collect(),
+            problem: None,
+        },
+        Kind::Process(command) => {
+            let mut external = match External::spawn(command, timeout){
+                Ok(external) => external,
+                Err(err) => return Results{ scores: vec![0; seeds.len()], problem: Some(err) },
+            };
+            let mut results = Results{ scores: Vec::with_capacity(seeds.len()), problem: None };
+            for seed in seeds{
+                if results.problem.is_some(){
+                    results.scores.push(0);
+                    continue;
+                }
+                let (score, problem) = external.play(preset, *seed);
+                results.scores.push(score);
+                results.problem = problem.map(|problem| format!("{problem} on seed {seed}"));
+            }
+            results
+        }
+    }
+}
+
+fn main(){
+    let mut games = 50_u32;
+    let mut first_seed = 0_u32;
+    let mut preset = RulePreset::Classic;
+    let mut timeout = MOVE_TIMEOUT;
+    let mut entrants = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next(){
+        let value = args.next().unwrap_or_else(|| usage());
+        match arg.as_str(){
+            "--games" => games = value.parse().unwrap_or_else(|_| usage()),
+            "--first-seed" => first_seed = value.parse().unwrap_or_else(|_| usage()),
+            "--preset" => preset = RulePreset::from_name(&value).unwrap_or_else(|| usage()),
+            "--move-timeout-ms" => timeout = Duration::from_millis(value.parse().unwrap_or_else(|_| usage())),
+            "--bot" => {
+                let name = builtin(&value, 0).map(|bot| bot.name()).unwrap_or_else(|| usage());
+                entrants.push(Entrant{ name, kind: Kind::Builtin(value) });
+            }
+            "--process" => {
+                let (name, command) = value.split_once('=').unwrap_or_else(|| usage());
+                let command: Vec<String> = command.split_whitespace().map(str::to_owned).collect();
+                if name.is_empty() || command.is_empty(){
+                    usage();
+                }
+                entrants.push(Entrant{ name: name.to_owned(), kind: Kind::Process(command) });
+            }
+            _ => usage(),
+        }
+    }
+    if entrants.len() < 2 || games == 0{
+        eprintln!("a tournament needs at least two entrants and one game");
+        usage();
+    }
+
+    let seeds: Vec<u32> = (0..games).map(|game| first_seed.wrapping_add(game)).collect();
+    let results: Vec<Results> = thread::scope(|scope| {
+        let running: Vec<_> = entrants.iter().map(|entrant| scope.spawn(|| run(entrant, preset, &seeds, timeout))).collect();
+        running.into_iter().map(|entrant| entrant.join().expect("an entrant's thread panicked")).collect()
+    });
+    report(&entrants, &results, preset, first_seed);
+}
+
+struct Standing{
+    entrant: usize,
+    mean: f64,
+    margin: f64, // half the width of the 95% confidence interval
+    wins: f64, // share of seeds beaten against all opponents, draws counting half
+}
+
+// The share of seeds on which a scored better than b, with draws counting half.
+fn win_rate(a: &[u16], b: &[u16]) -> f64{
+    let points: f64 = a.iter().zip(b).map(|(a, b)| match a.cmp(b){
+        std::cmp::Ordering::Greater => 1.,
+        std::cmp::Ordering::Equal => 0.5,
+        std::cmp::Ordering::Less => 0.,
+    }).sum();
+    points / a.len().max(1) as f64
+}
+
+fn report(entrants: &[Entrant], results: &[Results], preset: RulePreset, first_seed: u32){
+    let games = results[0].scores.len();
+    let width = entrants.iter().map(|entrant| entrant.name.len()).max().unwrap_or(0).max(7);
+    let mut standings: Vec<Standing> = results.iter().enumerate().map(|(entrant, result)| {
+        let n = games as f64;
+        let mean = result.scores.iter().map(|score| *score as f64).sum::<f64>() / n;
+        let variance = result.scores.iter().map(|score| (*score as f64 - mean).powi(2)).sum::<f64>() / (n - 1.).max(1.);
+        let opponents = (0..results.len()).filter(|other| *other != entrant);
+        let wins = opponents.map(|other| win_rate(&result.scores, &results[other].scores)).sum::<f64>() / (results.len() - 1) as f64;
+        Standing{ entrant, mean, margin: Z_95 * (variance / n).sqrt(), wins }
+    }).collect();
+    standings.sort_by(|a, b| b.mean.total_cmp(&a.mean).then(b.wins.total_cmp(&a.wins)));
+
+    println!("{} entrants, {games} {} games from seed {first_seed}", entrants.len(), preset.name());
+    println!();
+    println!("rank  {:<width$}  mean    95% interval   won", "entrant");
+    for (rank, standing) in standings.iter().enumerate(){
+        println!("{:>4}  {:<width$}  {:>6.2}  {:>5.2} - {:<5.2}  {:>4.0}%", rank + 1, entrants[standing.entrant].name,
+            standing.mean, standing.mean - standing.margin, standing.mean + standing.margin, standing.wins * 100.);
+    }
+
+    println!();
+    println!("head to head, how often the row beat the column on the same seed (draws count half)");
+    print!("{:<width$}", "");
+    for standing in standings.iter(){
+        print!("  {:>width$}", entrants[standing.entrant].name);
+    }
+    println!();
+    for row in standings.iter(){
+        print!("{:<width$}", entrants[row.entrant].name);
+        for column in standings.iter(){
+            if row.entrant == column.entrant{
+                print!("  {:>width$}", "-");
+            } else {
+                let rate = win_rate(&results[row.entrant].scores, &results[column.entrant].scores);
+                print!("  {:>width$}", format!("{:.0}%", rate * 100.));
+            }
+        }
+        println!();
+    }
+
+    for (entrant, result) in entrants.iter().zip(results){
+        if let Some(problem) = &result.problem{
+            println!();
+            println!("{} forfeited: {problem}", entrant.name);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests{
+    use super::*;
+
+    fn external(script: &str, timeout: Duration) -> External{
+        External::spawn(&["sh".to_owned(), "-c".to_owned(), script.to_owned()], timeout).unwrap()
+    }
+
+    #[test]
+    fn a_hung_process_forfeits_on_time(){
+        let started = Instant::now();
+        let (score, problem) = external("sleep 30", Duration::from_millis(200)).play(RulePreset::Quick, 1);
+        assert_eq!((score, problem.as_deref()), (0, Some("took longer than 200 ms over a move")));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn a_process_that_exits_forfeits(){
+        let (score, problem) = external("exit 0", MOVE_TIMEOUT).play(RulePreset::Quick, 1);
+        assert_eq!((score, problem.as_deref()), (0, Some("exited")));
+    }
+
+    #[test]
+    fn a_process_that_plays_on_finishes_the_game(){
+        // Claims with the first slot every time, answering each reply with the next claim.
+        let script = r#"while read -r reply; do echo '{"cmd":"claim","slot":0}'; done"#;
+        let (_, problem) = external(script, MOVE_TIMEOUT).play(RulePreset::Quick, 1);
+        assert_eq!(problem, None);
+    }
+}
@@ -0,0 +1,146 @@
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use crate::{rules::{Game, Move}, solver::Solver};
+
+// Something that picks moves. Bots see the whole Game, but only the solver's oracle is
+// meant to look at the order of the deck.
+pub trait Bot{
+    fn name(&self) -> String;
+    fn choose(&mut self, game: &Game) -> Move;
+}
+
+// Plays a game to the end and returns it.
+pub fn play_out(mut game: Game, bot: &mut dyn Bot) -> Game{
+    while !game.is_over(){
+        let mv = bot.choose(&game);
+        game.apply(mv).expect("bots only pick legal moves");
+    }
+    game
+}
+
+pub struct RandomBot{
+    rng: ChaCha8Rng,
+}
+
+impl RandomBot{
+    pub fn new(seed: u64) -> Self{
+        RandomBot{ rng: ChaCha8Rng::seed_from_u64(seed) }
+    }
+}
+
+impl Bot for RandomBot{
+    fn name(&self) -> String{
+        "random".to_owned()
+    }
+
+    fn choose(&mut self, game: &Game) -> Move{
+        *game.legal_moves().choose(&mut self.rng).expect("the game is not over")
+    }
+}
+
+// Claims whenever a world is balanced, otherwise pushes a world as close to 0 as it can.
+pub fn greedy_move(game: &Game, rng: &mut impl Rng) -> Move{
+    let hand = game.hand();
+    let balanced = game.balanced_worlds().len();
+    let mut best = Vec::new();
+    let mut best_score = i32::MIN;
+    for mv in game.distinct_moves(){
+        let card = hand[mv.slot() as usize];
+        let score = match mv{
+            Move::Claim{ .. } => balanced as i32 * 100 - 50,
+            Move::Push{ world, .. } => {
+                let pol = game.polarity[world as usize];
+                let pushed = if game.pleroma { pol + card } else { pol - card }.clamp(-game.rules.clamp, game.rules.clamp);
+                if pol == 0 { -20 } else { pol.abs() as i32 - pushed.abs() as i32 * 2 }
+            }
+        };
+        if score > best_score{
+            best_score = score;
+            best.clear();
+        }
+        if score == best_score{
+            best.push(mv);
+        }
+    }
+    *best.choose(rng).expect("the game is not over")
+}
+
+pub struct GreedyBot{
+    rng: ChaCha8Rng,
+}
+
+impl GreedyBot{
+    pub fn new(seed: u64) -> Self{
+        GreedyBot{ rng: ChaCha8Rng::seed_from_u64(seed) }
+    }
+}
+
+impl Bot for GreedyBot{
+    fn name(&self) -> String{
+        "greedy".to_owned()
+    }
+
+    fn choose(&mut self, game: &Game) -> Move{
+        greedy_move(game, &mut self.rng)
+    }
+}
+
+// Reads the deck and plays perfectly, as an upper bound for the others.
+#[derive(Default)]
+pub struct OracleBot{
+    solver: Solver,
+}
+
+impl OracleBot{
+    pub fn new() -> Self{
+        OracleBot::default()
+    }
+}
+
+impl Bot for OracleBot{
+    fn name(&self) -> String{
+        "oracle".to_owned()
+    }
+
+    fn choose(&mut self, game: &Game) -> Move{
+        self.solver.best_move(game).expect("the game is not over").0
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use crate::{mcts::{Budget, Mcts}, rules::{RulePreset, Rules}};
+    use super::*;
+
+    fn short_game(seed: u32) -> Game{ // the oracle solves the whole deck on its first move
+        Game::new(Rules{ deck_size: 6, ..RulePreset::Quick.rules() }, seed)
+    }
+
+    fn bots(seed: u64) -> Vec<Box<dyn Bot>>{
+        vec![Box::new(RandomBot::new(seed)), Box::new(GreedyBot::new(seed)), Box::new(OracleBot::new()), Box::new(Mcts::new(Budget::iterations(50), seed))]
+    }
+
+    #[test]
+    fn bots_only_pick_legal_moves(){
+        for mut bot in bots(1){
+            for seed in 0..3{
+                let mut game = short_game(seed);
+                while !game.is_over(){
+                    let mv = bot.choose(&game);
+                    assert!(game.legal_moves().contains(&mv), "{} picked {mv:?}", bot.name());
+                    game.apply(mv).unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_same_seed_makes_the_same_choices(){
+        for (mut first, mut second) in bots(7).into_iter().zip(bots(7)){
+            for seed in 0..3{
+                let (a, b) = (play_out(short_game(seed), &mut *first), play_out(short_game(seed), &mut *second));
+                assert_eq!(a, b, "{} played two ways", first.name());
+            }
+        }
+    }
+}
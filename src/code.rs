@@ -0,0 +1,268 @@
+use std::fmt;
+use crate::rules::{Game, Mode, Move, MoveError, RulePreset, HAND_SIZE, WORLDS};
+
+// A game as a short string: "PK-" then Crockford base32 of
+// [version][preset][mode][seed, 4 bytes big endian][one byte per move][CRC-16 of everything before].
+// A move byte is the hand slot times 8 plus the target world, or plus WORLDS for a claim.
+// Version 1 codes have no mode byte and are always solitaire games.
+
+const PREFIX: &str = "PK-";
+const VERSION: u8 = 2;
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const GROUP: usize = 5; // a dash every few characters, to make reading a code aloud easier
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GameCode{
+    pub preset: RulePreset,
+    pub mode: Mode,
+    pub seed: u32,
+    pub moves: Vec<Move>, // may be empty, or stop before the end of the game
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CodeError{
+    MissingPrefix,
+    BadCharacter(char),
+    TooShort,
+    Checksum,
+    UnsupportedVersion(u8),
+    UnknownPreset(u8),
+    UnknownMode(u8),
+    BadMove(usize),
+    IllegalMove(usize, MoveError),
+}
+
+impl fmt::Display for CodeError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            CodeError::MissingPrefix => write!(f, "game codes start with {PREFIX}"),
+            CodeError::BadCharacter(c) => write!(f, "'{c}' cannot appear in a game code"),
+            CodeError::TooShort => write!(f, "the code is too short, part of it is missing"),
+            CodeError::Checksum => write!(f, "the code does not add up, check it for typos"),
+            CodeError::UnsupportedVersion(version) => write!(f, "the code is from a newer version of the game (format {version})"),
+            CodeError::UnknownPreset(preset) => write!(f, "unknown rule preset {preset}"),
+            CodeError::UnknownMode(mode) => write!(f, "unknown game mode {mode}"),
+            CodeError::BadMove(turn) => write!(f, "turn {} is not a move", turn + 1),
+            CodeError::IllegalMove(turn, err) => write!(f, "turn {} cannot be played: {err}", turn + 1),
+        }
+    }
+}
+
+impl std::error::Error for CodeError{}
+
+fn crc16(bytes: &[u8]) -> u16{ // CRC-16/CCITT-FALSE
+    let mut crc = 0xffff_u16;
+    for byte in bytes{
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8{
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+fn move_byte(mv: Move) -> u8{
+    match mv{
+        Move::Push{ slot, world } => slot * 8 + world,
+        Move::Claim{ slot } => slot * 8 + WORLDS as u8,
+    }
+}
+
+fn byte_move(byte: u8) -> Option<Move>{
+    let (slot, target) = (byte / 8, byte % 8);
+    if slot as usize >= HAND_SIZE{
+        return None;
+    }
+    match target as usize{
+        t if t < WORLDS => Some(Move::Push{ slot, world: target }),
+        t if t == WORLDS => Some(Move::Claim{ slot }),
+        _ => None,
+    }
+}
+
+fn to_base32(bytes: &[u8]) -> String{
+    let mut text = String::new();
+    let (mut buffer, mut bits) = (0_u32, 0);
+    for byte in bytes{
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5{
+            bits -= 5;
+            text.push(ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0{
+        text.push(ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    text
+}
+
+fn from_base32(text: &str) -> Result<Vec<u8>, CodeError>{
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0_u32, 0);
+    for c in text.chars(){
+        let c = match c.to_ascii_uppercase(){ // Crockford's forgiving reading of lookalikes
+            '-' | ' ' => continue,
+            'O' => '0',
+            'I' | 'L' => '1',
+            c => c,
+        };
+        let value = ALPHABET.iter().position(|a| *a as char == c).ok_or(CodeError::BadCharacter(c))?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8{
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    if buffer & ((1 << bits) - 1) != 0{ // encode pads with zeros, so a typo in the last character shows
+        return Err(CodeError::Checksum);
+    }
+    Ok(bytes)
+}
+
+impl GameCode{
+    pub fn new(preset: RulePreset, mode: Mode, seed: u32) -> Self{
+        GameCode{ preset, mode, seed, moves: Vec::new() }
+    }
+
+    pub fn encode(&self) -> String{
+        let preset = RulePreset::ALL.iter().position(|preset| *preset == self.preset).expect("every preset is listed") as u8;
+        let mode = Mode::ALL.iter().position(|mode| *mode == self.mode).expect("every mode is listed") as u8;
+        let mut bytes = vec![VERSION, preset, mode];
+        bytes.extend(self.seed.to_be_bytes());
+        bytes.extend(self.moves.iter().map(|mv| move_byte(*mv)));
+        bytes.extend(crc16(&bytes).to_be_bytes());
+        let body = to_base32(&bytes);
+        let groups: Vec<&str> = body.as_bytes().chunks(GROUP)
+            .map(|chunk| std::str::from_utf8(chunk).expect("base32 is ascii"))
+            .collect();
+        format!("{PREFIX}{}", groups.join("-"))
+    }
+
+    pub fn decode(text: &str) -> Result<Self, CodeError>{
+        let text = text.trim();
+        let body = text.get(..PREFIX.len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case(PREFIX))
+            .map(|_| &text[PREFIX.len()..])
+            .ok_or(CodeError::MissingPrefix)?;
+        let bytes = from_base32(body)?;
+        if bytes.len() < 8{
+            return Err(CodeError::TooShort);
+        }
+        let (data, crc) = bytes.split_at(bytes.len() - 2);
+        if crc16(data).to_be_bytes() != crc{
+            return Err(CodeError::Checksum);
+        }
+        let (mode, data) = match data[0]{
+            1 => (Mode::Solitaire, &data[2..]),
+            VERSION if data.len() > 2 => (*Mode::ALL.get(data[2] as usize).ok_or(CodeError::UnknownMode(data[2]))?, &data[3..]),
+            VERSION => return Err(CodeError::TooShort),
+            version => return Err(CodeError::UnsupportedVersion(version)),
+        };
+        let preset = *RulePreset::ALL.get(bytes[1] as usize).ok_or(CodeError::UnknownPreset(bytes[1]))?;
+        if data.len() < 4{
+            return Err(CodeError::TooShort);
+        }
+        let seed = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        let moves = data[4..].iter().enumerate()
+            .map(|(turn, byte)| byte_move(*byte).ok_or(CodeError::BadMove(turn)))
+            .collect::<Result<_, _>>()?;
+        Ok(GameCode{ preset, mode, seed, moves })
+    }
+
+    // The game after every move of the code, which also proves the moves fit the deal.
+    pub fn replay(&self) -> Result<Game, CodeError>{
+        let mut game = Game::deal(self.preset.rules(), self.seed, self.mode);
+        for (turn, mv) in self.moves.iter().enumerate(){
+            game.apply(*mv).map_err(|err| CodeError::IllegalMove(turn, err))?;
+        }
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn sample() -> GameCode{
+        let moves = vec![Move::Push{ slot: 0, world: 1 }, Move::Claim{ slot: 3 }, Move::Push{ slot: 2, world: 3 }];
+        GameCode{ preset: RulePreset::Marathon, mode: Mode::Solitaire, seed: 0xdead_beef, moves }
+    }
+
+    // A code for raw bytes, with a valid checksum whatever they hold.
+    fn code_of(mut bytes: Vec<u8>) -> String{
+        bytes.extend(crc16(&bytes).to_be_bytes());
+        format!("{PREFIX}{}", to_base32(&bytes))
+    }
+
+    #[test]
+    fn codes_round_trip_with_and_without_moves(){
+        for code in [GameCode::new(RulePreset::Classic, Mode::Solitaire, 0), GameCode::new(RulePreset::Tight, Mode::Hotseat, u32::MAX), sample()]{
+            let text = code.encode();
+            assert!(text.starts_with(PREFIX));
+            assert_eq!(GameCode::decode(&text), Ok(code.clone()));
+            assert_eq!(GameCode::decode(&format!("  {}  ", text.to_lowercase())), Ok(code));
+        }
+    }
+
+    #[test]
+    fn every_one_character_typo_is_caught(){
+        let text = sample().encode();
+        for (at, original) in text.char_indices().skip(PREFIX.len()).filter(|(_, c)| *c != '-'){
+            for typo in ALPHABET.iter().map(|c| *c as char).filter(|c| *c != original){
+                let mistyped = format!("{}{typo}{}", &text[..at], &text[at + 1..]);
+                assert!(GameCode::decode(&mistyped).is_err(), "{mistyped} was accepted");
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_versions_are_refused(){
+        let text = code_of(vec![VERSION + 1, 0, 0, 0, 0, 0, 7]);
+        assert_eq!(GameCode::decode(&text), Err(CodeError::UnsupportedVersion(VERSION + 1)));
+        let text = code_of(vec![VERSION, 0, Mode::ALL.len() as u8, 0, 0, 0, 7]);
+        assert_eq!(GameCode::decode(&text), Err(CodeError::UnknownMode(Mode::ALL.len() as u8)));
+    }
+
+    #[test]
+    fn version_1_codes_are_solitaire_games(){
+        let text = code_of(vec![1, 2, 0xde, 0xad, 0xbe, 0xef, 1, 3 * 8 + WORLDS as u8, 2 * 8 + 3]);
+        assert_eq!(GameCode::decode(&text), Ok(sample()));
+    }
+
+    #[test]
+    fn versus_games_replay_in_their_mode(){
+        for mode in [Mode::Hotseat, Mode::Dimensions]{
+            let mut game = Game::deal(RulePreset::Quick.rules(), 9, mode);
+            let mut code = GameCode::new(RulePreset::Quick, mode, 9);
+            while !game.is_over(){
+                let mv = game.distinct_moves()[code.moves.len() % 2];
+                game.apply(mv).unwrap();
+                code.moves.push(mv);
+            }
+            let replayed = GameCode::decode(&code.encode()).and_then(|code| code.replay()).unwrap();
+            assert_eq!((replayed.mode, replayed.scores, replayed.score), (mode, game.scores, game.score));
+        }
+    }
+
+    #[test]
+    fn truncated_codes_are_refused(){
+        let text = sample().encode();
+        for len in PREFIX.len()..text.len(){
+            let result = GameCode::decode(&text[..len]);
+            assert!(result.is_err(), "{} was accepted", &text[..len]);
+        }
+        assert_eq!(GameCode::decode("PK-0000"), Err(CodeError::TooShort));
+        assert_eq!(GameCode::decode("0123456789"), Err(CodeError::MissingPrefix));
+    }
+
+    #[test]
+    fn moves_must_fit_the_deal(){
+        let code = code_of(vec![VERSION, 0, 0, 0, 0, 0, 7, 5 * 8]);
+        assert_eq!(GameCode::decode(&code), Err(CodeError::BadMove(0)));
+        let mut code = GameCode::new(RulePreset::Quick, Mode::Solitaire, 3);
+        code.moves = vec![Move::Claim{ slot: 0 }; 13]; // Quick deals 12 cards
+        assert_eq!(code.replay(), Err(CodeError::IllegalMove(12, MoveError::GameOver)));
+    }
+}
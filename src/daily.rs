@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::{date, storage};
+
+const DAILY_FILE: &str = "daily.json";
+
+// Days since the unix epoch, in UTC so that everyone rolls over to the next puzzle at once.
+pub fn today() -> u64{
+    date::now() / 86_400
+}
+
+// The same deal for everyone on a given day. SplitMix64, so neighbouring days look unrelated.
+pub fn daily_seed(day: u64) -> u32{
+    let mut z = day.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)) as u32
+}
+
+// The attempt counts as soon as it is dealt, so quitting a bad start does not earn a retry.
+#[derive(Resource, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DailyRecord{
+    pub last_day: Option<u64>,
+    pub last_score: Option<u16>, // None until that day's game is finished
+    pub streak: u32, // consecutive days with an attempt, ending on last_day
+    pub best_streak: u32,
+}
+
+impl DailyRecord{
+    pub fn load() -> Self{
+        storage::load(DAILY_FILE).unwrap_or_default()
+    }
+
+    pub fn save(&self){
+        storage::save(DAILY_FILE, self);
+    }
+
+    pub fn played(&self, day: u64) -> bool{
+        self.last_day == Some(day)
+    }
+
+    // The streak as it stands today, which is broken once a whole day goes by without playing.
+    pub fn current_streak(&self, day: u64) -> u32{
+        match self.last_day{
+            Some(last) if last + 1 >= day => self.streak,
+            _ => 0,
+        }
+    }
+
+    pub fn start(&mut self, day: u64){
+        if self.record_start(day){
+            self.save();
+        }
+    }
+
+    pub fn finish(&mut self, day: u64, score: u16){
+        if self.record_finish(day, score){
+            self.save();
+        }
+    }
+
+    // The bookkeeping of start and finish, true when something changed.
+    fn record_start(&mut self, day: u64) -> bool{
+        if self.played(day){
+            return false;
+        }
+        self.streak = if self.last_day == Some(day.wrapping_sub(1)) { self.streak + 1 } else { 1 };
+        self.best_streak = self.best_streak.max(self.streak);
+        self.last_day = Some(day);
+        self.last_score = None;
+        true
+    }
+
+    fn record_finish(&mut self, day: u64, score: u16) -> bool{
+        if self.last_day != Some(day) || self.last_score.is_some(){
+            return false;
+        }
+        self.last_score = Some(score);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn each_day_has_its_own_seed(){
+        assert_eq!(daily_seed(20_000), daily_seed(20_000));
+        let seeds: Vec<u32> = (20_000..20_030).map(daily_seed).collect();
+        for (index, seed) in seeds.iter().enumerate(){
+            assert!(!seeds[index + 1..].contains(seed));
+        }
+    }
+
+    #[test]
+    fn streaks_grow_on_consecutive_days_and_reset_after_a_gap(){
+        let mut record = DailyRecord::default();
+        assert!(record.record_start(10));
+        assert!(record.record_start(11));
+        assert!(!record.record_start(11), "a second attempt the same day changes nothing");
+        assert_eq!((record.streak, record.best_streak), (2, 2));
+        assert_eq!(record.current_streak(12), 2);
+        assert_eq!(record.current_streak(13), 0);
+        assert!(record.record_start(13));
+        assert_eq!((record.streak, record.best_streak, record.last_day), (1, 2, Some(13)));
+    }
+
+    #[test]
+    fn only_the_first_finish_of_the_day_counts(){
+        let mut record = DailyRecord::default();
+        assert!(!record.record_finish(5, 3), "never started");
+        record.record_start(5);
+        assert!(record.record_finish(5, 3));
+        assert!(!record.record_finish(5, 9));
+        assert!(!record.record_finish(6, 9));
+        assert_eq!(record.last_score, Some(3));
+        record.record_start(6);
+        assert_eq!(record.last_score, None);
+    }
+}
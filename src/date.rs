@@ -0,0 +1,33 @@
+// Wall-clock time as unix seconds, and just enough of the calendar to print a day. Always UTC.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> u64{
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> u64{
+    (js_sys::Date::now() / 1000.) as u64
+}
+
+pub fn format_date(secs: u64) -> String{
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+// Howard Hinnant's days_from_civil, the other way around.
+fn civil_from_days(days: i64) -> (i64, u32, u32){
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
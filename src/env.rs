@@ -0,0 +1,165 @@
+use std::fmt;
+use crate::rules::{Game, Move, MoveError, Rules, HAND_SIZE, MOVE_COUNT, WORLDS};
+
+// The game as a reinforcement learning environment: reset, then step with action numbers
+// (Move::index) until done. Observations are fixed-size and scaled to about -1..1:
+// [polarity / clamp; WORLDS][active dimension, 0 Kenoma or 1 Pleroma]
+// [Kenoma hand / max card; HAND_SIZE][Pleroma hand / max card; HAND_SIZE][cards left / deck size]
+
+pub const OBSERVATION_SIZE: usize = WORLDS + 1 + HAND_SIZE * 2 + 1;
+
+pub type Observation = [f32; OBSERVATION_SIZE];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Step{
+    pub observation: Observation,
+    pub reward: f32, // worlds scored by this action
+    pub done: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StepError{
+    NoSuchAction(usize),
+    Illegal(MoveError),
+}
+
+impl fmt::Display for StepError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            StepError::NoSuchAction(action) => write!(f, "actions go from 0 to {}, not {action}", MOVE_COUNT - 1),
+            StepError::Illegal(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for StepError{}
+
+pub struct Env{
+    rules: Rules,
+    game: Game,
+}
+
+impl Env{
+    // Starts on seed 0, so stepping before the first reset is still well defined.
+    pub fn new(rules: Rules) -> Self{
+        let game = Game::new(rules.clone(), 0);
+        Env{ rules, game }
+    }
+
+    pub fn reset(&mut self, seed: u32) -> Observation{
+        self.game = Game::new(self.rules.clone(), seed);
+        self.observation()
+    }
+
+    pub fn step(&mut self, action: usize) -> Result<Step, StepError>{
+        let mv = Move::from_index(action).ok_or(StepError::NoSuchAction(action))?;
+        let outcome = self.game.apply(mv).map_err(StepError::Illegal)?;
+        Ok(Step{
+            observation: self.observation(),
+            reward: outcome.balanced.len() as f32,
+            done: self.game.is_over(),
+        })
+    }
+
+    pub fn game(&self) -> &Game{
+        &self.game
+    }
+
+    pub fn observation(&self) -> Observation{
+        let game = &self.game;
+        let mut observation = [0.; OBSERVATION_SIZE];
+        for (world, polarity) in game.polarity.iter().enumerate(){
+            observation[world] = *polarity as f32 / game.rules.clamp as f32;
+        }
+        observation[WORLDS] = game.pleroma as u8 as f32;
+        for (slot, card) in game.hands.iter().flatten().enumerate(){
+            observation[WORLDS + 1 + slot] = *card as f32 / game.rules.max_card as f32;
+        }
+        observation[OBSERVATION_SIZE - 1] = game.deck.len() as f32 / game.rules.deck_size.max(1) as f32;
+        observation
+    }
+
+    // True for the actions step accepts. Every move is legal while cards remain, so this
+    // is all or nothing; distinct_mask is the one worth feeding a policy.
+    pub fn action_mask(&self) -> [bool; MOVE_COUNT]{
+        let mut mask = [false; MOVE_COUNT];
+        for mv in self.game.legal_moves(){
+            mask[mv.index()] = true;
+        }
+        mask
+    }
+
+    // The legal actions minus those that lead to the same position as an earlier one,
+    // such as playing either of two cards of equal value.
+    pub fn distinct_mask(&self) -> [bool; MOVE_COUNT]{
+        let mut mask = [false; MOVE_COUNT];
+        for mv in self.game.distinct_moves(){
+            mask[mv.index()] = true;
+        }
+        mask
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use crate::rules::RulePreset;
+    use super::*;
+
+    #[test]
+    fn reset_deals_the_seed_and_lays_out_the_observation(){
+        let rules = RulePreset::Classic.rules();
+        let mut env = Env::new(rules.clone());
+        let observation = env.reset(17);
+        assert_eq!(Env::new(rules.clone()).reset(17), observation);
+        let game = Game::new(rules, 17);
+        let mut expected = Vec::new();
+        expected.extend(game.polarity.iter().map(|polarity| *polarity as f32 / game.rules.clamp as f32));
+        expected.push(0.);
+        expected.extend(game.hands.iter().flatten().map(|card| *card as f32 / game.rules.max_card as f32));
+        expected.push(1.);
+        assert_eq!(observation.to_vec(), expected);
+    }
+
+    #[test]
+    fn steps_reward_claims_and_end_with_the_deck(){
+        let mut env = Env::new(RulePreset::Quick.rules());
+        env.reset(4);
+        let mut total = 0.;
+        for turn in 0..env.game().rules.deck_size{
+            let mut next = env.game().clone();
+            let mv = if turn % 3 == 2 { Move::Claim{ slot: 1 } } else { Move::Push{ slot: 0, world: turn as u8 % 4 } };
+            let scored = next.apply(mv).unwrap().balanced.len() as f32;
+            let step = env.step(mv.index()).unwrap();
+            assert_eq!((step.reward, step.done), (scored, next.is_over()));
+            assert_eq!(step.observation, env.observation());
+            assert_eq!(step.done, turn + 1 == env.game().rules.deck_size);
+            total += step.reward;
+        }
+        assert_eq!(total, env.game().score as f32);
+    }
+
+    #[test]
+    fn bad_actions_are_refused(){
+        let mut env = Env::new(RulePreset::Quick.rules());
+        env.reset(4);
+        assert_eq!(env.step(MOVE_COUNT), Err(StepError::NoSuchAction(MOVE_COUNT)));
+        while !env.game().is_over(){
+            env.step(Move::Claim{ slot: 0 }.index()).unwrap();
+        }
+        assert_eq!(env.step(0), Err(StepError::Illegal(MoveError::GameOver)));
+        assert_eq!(env.action_mask(), [false; MOVE_COUNT]);
+    }
+
+    #[test]
+    fn masks_match_the_moves(){
+        let mut env = Env::new(RulePreset::Classic.rules());
+        env.reset(9);
+        for _ in 0..10{
+            let game = env.game().clone();
+            let masked = |mask: [bool; MOVE_COUNT]| (0..MOVE_COUNT).filter(|action| mask[*action]).filter_map(Move::from_index).collect::<Vec<_>>();
+            assert_eq!(masked(env.action_mask()), game.legal_moves());
+            assert_eq!(masked(env.distinct_mask()), game.distinct_moves());
+            env.step(game.distinct_moves()[1].index()).unwrap();
+        }
+    }
+}
@@ -0,0 +1,314 @@
+// A C interface to the rules engine, for driving it from other languages through the cdylib.
+// include/pleromakenoma.h declares it; regenerate it with
+//   cbindgen --config cbindgen.toml --output include/pleromakenoma.h
+// after changing anything here. Existing functions and structs keep their layout, new ones are
+// added alongside and PK_API_VERSION goes up.
+//
+// Games are opaque pointers from pk_game_new or pk_game_new_preset and must be freed with
+// pk_game_free. Nothing here panics or holds on to pointers it was given.
+
+use std::{ffi::c_char, ptr, slice};
+use crate::rules::{Game, Move, MoveError, RulePreset, Rules, HAND_SIZE, MOVE_COUNT, WORLDS};
+
+pub const PK_API_VERSION: u32 = 2;
+
+pub const PK_OK: i32 = 0;
+pub const PK_ERR_NULL: i32 = -1;
+pub const PK_ERR_GAME_OVER: i32 = -2;
+pub const PK_ERR_NO_SUCH_SLOT: i32 = -3;
+pub const PK_ERR_NO_SUCH_WORLD: i32 = -4;
+pub const PK_ERR_BAD_KIND: i32 = -5;
+pub const PK_ERR_BAD_GAME: i32 = -6; // since version 2
+
+pub const PK_MOVE_PUSH: u8 = 0;
+pub const PK_MOVE_CLAIM: u8 = 1;
+
+// Spelled out so that the header gets numbers rather than names C does not know.
+pub const PK_WORLDS: usize = 4;
+pub const PK_HAND_SIZE: usize = 4;
+pub const PK_MOVE_COUNT: usize = 20;
+const _: () = assert!(PK_WORLDS == WORLDS && PK_HAND_SIZE == HAND_SIZE && PK_MOVE_COUNT == MOVE_COUNT);
+
+pub struct PkGame(Game);
+
+#[repr(C)]
+pub struct PkRules{
+    pub deck_size: u16,
+    pub clamp: i8,
+    pub max_card: i8,
+    pub starting_offsets: *const i8,
+    pub offset_count: usize,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PkMove{
+    pub kind: u8, // PK_MOVE_PUSH or PK_MOVE_CLAIM
+    pub slot: u8,
+    pub world: u8, // ignored for claims
+}
+
+#[repr(C)]
+pub struct PkState{
+    pub polarity: [i8; PK_WORLDS],
+    pub hands: [[i8; PK_HAND_SIZE]; 2], // Kenoma then Pleroma
+    pub pleroma: bool, // the active dimension
+    pub score: u16,
+    pub turn: u16,
+    pub cards_left: u16,
+    pub over: bool,
+}
+
+#[repr(C)]
+pub struct PkOutcome{
+    pub card: i8,
+    pub balanced_count: u8,
+    pub balanced: [u8; PK_WORLDS], // the first balanced_count entries are the worlds a claim scored
+    pub clamped: bool,
+    pub drew: bool,
+    pub drawn: i8, // only meaningful when drew is set
+}
+
+impl From<Move> for PkMove{
+    fn from(mv: Move) -> Self{
+        match mv{
+            Move::Push{ slot, world } => PkMove{ kind: PK_MOVE_PUSH, slot, world },
+            Move::Claim{ slot } => PkMove{ kind: PK_MOVE_CLAIM, slot, world: 0 },
+        }
+    }
+}
+
+fn error_code(err: MoveError) -> i32{
+    match err{
+        MoveError::BadGame => PK_ERR_BAD_GAME,
+        MoveError::GameOver => PK_ERR_GAME_OVER,
+        MoveError::NoSuchSlot(_) => PK_ERR_NO_SUCH_SLOT,
+        MoveError::NoSuchWorld(_) => PK_ERR_NO_SUCH_WORLD,
+    }
+}
+
+fn into_handle(game: Game) -> *mut PkGame{
+    Box::into_raw(Box::new(PkGame(game)))
+}
+
+/// Deals a game with custom rules, or returns null if the rules cannot deal one: the clamp
+/// and max_card must be 1 to 6, and every starting offset within the clamp.
+///
+/// # Safety
+/// `rules` must be null or point to a PkRules whose `starting_offsets` holds `offset_count` values.
+#[no_mangle]
+pub unsafe extern "C" fn pk_game_new(rules: *const PkRules, seed: u32) -> *mut PkGame{
+    let Some(rules) = rules.as_ref() else { return ptr::null_mut() };
+    if rules.starting_offsets.is_null(){
+        return ptr::null_mut();
+    }
+    let rules = Rules{
+        deck_size: rules.deck_size,
+        clamp: rules.clamp,
+        starting_offsets: slice::from_raw_parts(rules.starting_offsets, rules.offset_count).to_vec(),
+        max_card: rules.max_card,
+    };
+    if !rules.is_valid(){
+        return ptr::null_mut();
+    }
+    into_handle(Game::new(rules, seed))
+}
+
+/// Deals a game under a preset: 0 Classic, 1 Quick, 2 Marathon, 3 Tight. Null for any other.
+#[no_mangle]
+pub extern "C" fn pk_game_new_preset(preset: u32, seed: u32) -> *mut PkGame{
+    match RulePreset::ALL.get(preset as usize){
+        Some(preset) => into_handle(Game::new(preset.rules(), seed)),
+        None => ptr::null_mut(),
+    }
+}
+
+/// # Safety
+/// `game` must be null or come from pk_game_new or pk_game_new_preset and not be freed already.
+#[no_mangle]
+pub unsafe extern "C" fn pk_game_free(game: *mut PkGame){
+    if !game.is_null(){
+        drop(Box::from_raw(game));
+    }
+}
+
+/// Writes up to `capacity` legal moves to `out` and returns how many there are in total,
+/// which is at most PK_MOVE_COUNT. Pass a null `out` to only count them.
+///
+/// # Safety
+/// `game` must be null or a live game, `out` null or room for `capacity` moves.
+#[no_mangle]
+pub unsafe extern "C" fn pk_game_legal_moves(game: *const PkGame, out: *mut PkMove, capacity: usize) -> usize{
+    let Some(PkGame(game)) = game.as_ref() else { return 0 };
+    let moves = game.legal_moves();
+    if !out.is_null(){
+        for (index, mv) in moves.iter().take(capacity).enumerate(){
+            out.add(index).write((*mv).into());
+        }
+    }
+    moves.len()
+}
+
+/// Plays a move and, if `outcome` is not null, describes what it did. Returns PK_OK or one of
+/// the PK_ERR codes, in which case the game is unchanged.
+///
+/// # Safety
+/// `game` must be null or a live game, `outcome` null or valid for writing one PkOutcome.
+#[no_mangle]
+pub unsafe extern "C" fn pk_game_apply(game: *mut PkGame, mv: PkMove, outcome: *mut PkOutcome) -> i32{
+    let Some(PkGame(game)) = game.as_mut() else { return PK_ERR_NULL };
+    let mv = match mv.kind{
+        PK_MOVE_PUSH => Move::Push{ slot: mv.slot, world: mv.world },
+        PK_MOVE_CLAIM => Move::Claim{ slot: mv.slot },
+        _ => return PK_ERR_BAD_KIND,
+    };
+    let result = match game.apply(mv){
+        Ok(result) => result,
+        Err(err) => return error_code(err),
+    };
+    if let Some(outcome) = outcome.as_mut(){
+        let mut balanced = [0; PK_WORLDS];
+        balanced[..result.balanced.len()].copy_from_slice(&result.balanced);
+        *outcome = PkOutcome{
+            card: result.card,
+            balanced_count: result.balanced.len() as u8,
+            balanced,
+            clamped: result.clamped,
+            drew: result.drawn.is_some(),
+            drawn: result.drawn.unwrap_or(0),
+        };
+    }
+    PK_OK
+}
+
+/// Copies the visible state of the game into `out`. The order of the deck stays hidden.
+///
+/// # Safety
+/// `game` must be null or a live game, `out` null or valid for writing one PkState.
+#[no_mangle]
+pub unsafe extern "C" fn pk_game_state(game: *const PkGame, out: *mut PkState) -> i32{
+    let (Some(PkGame(game)), Some(out)) = (game.as_ref(), out.as_mut()) else { return PK_ERR_NULL };
+    *out = PkState{
+        polarity: game.polarity,
+        hands: game.hands,
+        pleroma: game.pleroma,
+        score: game.score,
+        turn: game.turn,
+        cards_left: game.deck.len() as u16,
+        over: game.is_over(),
+    };
+    PK_OK
+}
+
+/// A static, nul-terminated description of a status code. Never free it.
+#[no_mangle]
+pub extern "C" fn pk_error_message(code: i32) -> *const c_char{
+    let message: &'static [u8] = match code{
+        PK_OK => b"ok\0",
+        PK_ERR_NULL => b"a required pointer was null\0",
+        PK_ERR_GAME_OVER => b"the game is over\0",
+        PK_ERR_NO_SUCH_SLOT => b"there is no such hand slot\0",
+        PK_ERR_NO_SUCH_WORLD => b"there is no such world\0",
+        PK_ERR_BAD_KIND => b"the move is neither a push nor a claim\0",
+        PK_ERR_BAD_GAME => b"the game is in a state the rules never reach\0",
+        _ => b"unknown error\0",
+    };
+    message.as_ptr() as *const c_char
+}
+
+#[no_mangle]
+pub extern "C" fn pk_api_version() -> u32{
+    PK_API_VERSION
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn pk_rules(offsets: &[i8], clamp: i8, max_card: i8) -> PkRules{
+        PkRules{ deck_size: 10, clamp, max_card, starting_offsets: offsets.as_ptr(), offset_count: offsets.len() }
+    }
+
+    unsafe fn game(handle: *mut PkGame) -> Game{
+        (*handle).0.clone()
+    }
+
+    #[test]
+    fn only_valid_rules_deal(){
+        let offsets = [-3, 3];
+        unsafe{
+            assert!(pk_game_new(ptr::null(), 1).is_null());
+            let mut rules = pk_rules(&offsets, 6, 6);
+            rules.starting_offsets = ptr::null();
+            assert!(pk_game_new(&rules, 1).is_null());
+            for rules in [pk_rules(&offsets, 0, 6), pk_rules(&offsets, 6, 7), pk_rules(&offsets, 2, 6), pk_rules(&[], 6, 6)]{
+                assert!(pk_game_new(&rules, 1).is_null());
+            }
+            let handle = pk_game_new(&pk_rules(&offsets, 6, 6), 1);
+            assert!(!handle.is_null());
+            assert_eq!(game(handle).rules.starting_offsets, offsets);
+            pk_game_free(handle);
+        }
+        assert!(pk_game_new_preset(RulePreset::ALL.len() as u32, 1).is_null());
+        assert!(pk_game_new_preset(u32::MAX, 1).is_null());
+    }
+
+    #[test]
+    fn legal_moves_fill_only_the_room_given(){
+        unsafe{
+            let handle = pk_game_new_preset(0, 5);
+            let count = game(handle).legal_moves().len();
+            assert_eq!(pk_game_legal_moves(handle, ptr::null_mut(), 0), count);
+            let unwritten = PkMove{ kind: 9, slot: 9, world: 9 };
+            let mut out = [unwritten; PK_MOVE_COUNT];
+            assert_eq!(pk_game_legal_moves(handle, out.as_mut_ptr(), 3), count);
+            assert!(out[..3].iter().all(|mv| mv.kind != 9));
+            assert!(out[3..].iter().all(|mv| mv.kind == 9));
+            assert_eq!(pk_game_legal_moves(ptr::null(), out.as_mut_ptr(), 3), 0);
+            pk_game_free(handle);
+        }
+    }
+
+    #[test]
+    fn refused_moves_leave_the_game_alone(){
+        unsafe{
+            let handle = pk_game_new_preset(1, 5);
+            let before = game(handle);
+            for (mv, code) in [
+                (PkMove{ kind: 2, slot: 0, world: 0 }, PK_ERR_BAD_KIND),
+                (PkMove{ kind: PK_MOVE_PUSH, slot: 4, world: 0 }, PK_ERR_NO_SUCH_SLOT),
+                (PkMove{ kind: PK_MOVE_PUSH, slot: 0, world: 4 }, PK_ERR_NO_SUCH_WORLD),
+            ]{
+                assert_eq!(pk_game_apply(handle, mv, ptr::null_mut()), code);
+                assert_eq!(game(handle), before);
+            }
+            let claim = PkMove{ kind: PK_MOVE_CLAIM, slot: 0, world: 0 };
+            while !game(handle).is_over(){
+                assert_eq!(pk_game_apply(handle, claim, ptr::null_mut()), PK_OK);
+            }
+            let over = game(handle);
+            assert_eq!(pk_game_apply(handle, claim, ptr::null_mut()), PK_ERR_GAME_OVER);
+            assert_eq!(game(handle), over);
+            assert_eq!(pk_game_apply(ptr::null_mut(), claim, ptr::null_mut()), PK_ERR_NULL);
+            pk_game_free(handle);
+        }
+    }
+
+    #[test]
+    fn state_mirrors_the_game(){
+        unsafe{
+            let handle = pk_game_new_preset(0, 8);
+            let mut outcome = PkOutcome{ card: 0, balanced_count: 0, balanced: [0; PK_WORLDS], clamped: false, drew: false, drawn: 0 };
+            assert_eq!(pk_game_apply(handle, PkMove{ kind: PK_MOVE_PUSH, slot: 2, world: 1 }, &mut outcome), PK_OK);
+            let mut state = PkState{ polarity: [0; PK_WORLDS], hands: [[0; PK_HAND_SIZE]; 2], pleroma: false, score: 0, turn: 0, cards_left: 0, over: true };
+            assert_eq!(pk_game_state(handle, &mut state), PK_OK);
+            let game = game(handle);
+            assert_eq!((state.polarity, state.hands, state.pleroma), (game.polarity, game.hands, game.pleroma));
+            assert_eq!((state.score, state.turn, state.cards_left, state.over), (game.score, 1, game.deck.len() as u16, false));
+            assert_eq!(Some(outcome.drawn).filter(|_| outcome.drew), Some(game.hand()[2]));
+            assert_eq!(pk_game_state(ptr::null(), &mut state), PK_ERR_NULL);
+            assert_eq!(pk_game_state(handle, ptr::null_mut()), PK_ERR_NULL);
+            pk_game_free(handle);
+        }
+    }
+}
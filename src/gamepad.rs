@@ -0,0 +1,114 @@
+use bevy::prelude::*;
+use crate::{input::{InputAction, InputQueue}, GameSettings, TextLabel};
+
+const STICK_PRESS: f32 = 0.5;
+const STICK_RELEASE: f32 = 0.3;
+
+// The gamepad moves a cursor over the four TextLabels, which already travel between the hand
+// and the worlds of whichever dimension is active, so one cursor serves every phase.
+#[derive(Resource, Default)]
+pub struct GamepadCursor{
+    pub index: u8,
+    pub active: bool, // a gamepad was used more recently than the keyboard
+    stick_held: bool,
+}
+
+impl GamepadCursor{
+    pub fn button_label(&self, action: InputAction) -> String{
+        match action{
+            InputAction::Claim => "X".to_owned(),
+            InputAction::SelectSlot(n) | InputAction::TargetWorld(n) if n == self.index => "A".to_owned(),
+            _ => String::new(),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct CursorHighlight{}
+
+pub fn spawn_cursor_highlight(mut commands: Commands, asset_server: Res<AssetServer>, mut texture_atlases: ResMut<Assets<TextureAtlas>>){
+    let img_path = "spritesheet.png".to_owned();
+    let texture_handle = asset_server.load(&img_path);
+    let texture_atlas = TextureAtlas::from_grid(
+        texture_handle,
+        Vec2::new(16.0, 16.0),
+        80, 2, None, None
+    );
+    let texture_atlas_handle = texture_atlases.add(texture_atlas);
+    commands.spawn((SpriteSheetBundle {
+        texture_atlas: texture_atlas_handle,
+        sprite: TextureAtlasSprite{
+            index : 9,
+            custom_size: Some(Vec2::new(28.0, 28.0)),
+            color: Color::rgb(0.6, 0.6, 0.6),
+            ..default()
+        },
+        visibility: Visibility::Hidden,
+        ..default()
+    },
+    CursorHighlight{},
+    ));
+}
+
+pub fn gamepad_input(
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    keys: Res<Input<KeyCode>>,
+    settings: Res<GameSettings>,
+    mut cursor: ResMut<GamepadCursor>,
+    mut queue: ResMut<InputQueue>,
+){
+    if keys.get_just_pressed().len() > 0{
+        cursor.active = false;
+    }
+    for gamepad in gamepads.iter(){
+        let pressed = |button_type| buttons.just_pressed(GamepadButton::new(gamepad, button_type));
+        let mut step = 0;
+        if pressed(GamepadButtonType::DPadLeft) || pressed(GamepadButtonType::DPadUp){
+            step = -1;
+        }
+        if pressed(GamepadButtonType::DPadRight) || pressed(GamepadButtonType::DPadDown){
+            step = 1;
+        }
+        let x = axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX)).unwrap_or(0.);
+        let y = axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY)).unwrap_or(0.);
+        let tilt = x.abs().max(y.abs());
+        if tilt < STICK_RELEASE{
+            cursor.stick_held = false;
+        }
+        else if tilt > STICK_PRESS && !cursor.stick_held{
+            cursor.stick_held = true;
+            step = if x > STICK_PRESS || y < -STICK_PRESS { 1 } else { -1 }; // right and down go forward
+        }
+        let confirm = pressed(GamepadButtonType::South);
+        let claim = pressed(GamepadButtonType::West);
+        if step == 0 && !confirm && !claim{
+            continue;
+        }
+        cursor.active = true;
+        cursor.index = (cursor.index as i8 + step).clamp(0, 3) as u8;
+        if confirm{
+            let press = vec![InputAction::SelectSlot(cursor.index), InputAction::TargetWorld(cursor.index)];
+            queue.push(&settings, press);
+        }
+        if claim{
+            queue.push(&settings, vec![InputAction::Claim]);
+        }
+    }
+}
+
+pub fn move_cursor_highlight(
+    cursor: Res<GamepadCursor>,
+    query_labels: Query<(&TextLabel, &Transform), Without<CursorHighlight>>,
+    mut query_highlight: Query<(&mut Transform, &mut Visibility), With<CursorHighlight>>,
+){
+    for (mut trans, mut visibility) in query_highlight.iter_mut(){
+        *visibility = if cursor.active { Visibility::Visible } else { Visibility::Hidden };
+        for (label, label_trans) in query_labels.iter(){
+            if label.number == cursor.index{
+                trans.translation = label_trans.translation;
+            }
+        }
+    }
+}
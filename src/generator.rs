@@ -0,0 +1,217 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use crate::{puzzle::{Goal, Puzzle, Verdict}, rules::{Game, Move, HAND_SIZE, MAX_CARD, WORLDS}, solver::Solver};
+
+// Fresh puzzles from random positions. A candidate is a random start and a short run of draws,
+// its goal is the most worlds perfect play can claim before the draws run out, as the solver
+// finds it. Every way of reaching that goal is then counted, and the candidates with a single
+// one are what the generator is after.
+//
+// Solutions are counted as lines of moves, with interchangeable cards and worlds counted once
+// (see Game::distinct_moves): two pushes of the same card onto two worlds on the same polarity
+// are one way of solving, not two, and so is the final claim whichever card makes it.
+
+const ATTEMPTS: u32 = 500; // candidates tried in search of a unique solution
+const SOLUTION_LIMIT: usize = 8; // counting stops here, a puzzle with that many is wide open anyway
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+pub enum Difficulty{
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+}
+
+impl Difficulty{
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+    pub fn name(self) -> &'static str{
+        match self{
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self>{
+        Difficulty::ALL.into_iter().find(|difficulty| difficulty.name().eq_ignore_ascii_case(name))
+    }
+
+    // How many moves the solution takes.
+    fn moves(self) -> (usize, usize){
+        match self{
+            Difficulty::Easy => (2, 3),
+            Difficulty::Medium => (4, 5),
+            Difficulty::Hard => (6, 7),
+        }
+    }
+
+    // The fewest different moves on offer per turn of the solution, on average.
+    fn branching(self) -> f32{
+        match self{
+            Difficulty::Easy => 0.,
+            Difficulty::Medium => 8.,
+            Difficulty::Hard => 12.,
+        }
+    }
+
+    fn fits(self, rating: &Rating) -> bool{
+        let (fewest, most) = self.moves();
+        (fewest..=most).contains(&rating.solution.len()) && rating.branching >= self.branching()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Rating{
+    pub solutions: usize, // up to the limit rate was given
+    pub solution: Vec<Move>, // the shortest of those found
+    pub branching: f32, // different moves on offer per turn along the solution, on average
+}
+
+struct Count<'a>{
+    puzzle: &'a Puzzle,
+    solver: &'a mut Solver,
+    limit: usize,
+    solutions: usize,
+    shortest: Option<Vec<Move>>,
+}
+
+impl Count<'_>{
+    fn walk(&mut self, game: &Game, line: &mut Vec<Move>){
+        // Every claim from a position scores the same worlds, so once one of them solves the
+        // puzzle the card it is made with is no real choice.
+        let mut solved_by_claim = false;
+        for mv in game.distinct_moves(){
+            if self.solutions >= self.limit{
+                return;
+            }
+            if solved_by_claim && matches!(mv, Move::Claim{ .. }){
+                continue;
+            }
+            // Lines that cannot reach the score any more are cut short by the solver.
+            if let Goal::Score{ worlds, .. } = self.puzzle.goal{
+                if game.score + self.solver.move_value(game, mv) < worlds{
+                    continue;
+                }
+            }
+            let mut next = game.clone();
+            let outcome = next.apply(mv).expect("distinct moves are legal");
+            line.push(mv);
+            match self.puzzle.verdict(&next, &outcome){
+                Verdict::Solved => {
+                    solved_by_claim |= matches!(mv, Move::Claim{ .. });
+                    self.solutions += 1;
+                    if self.shortest.as_ref().is_none_or(|shortest| line.len() < shortest.len()){
+                        self.shortest = Some(line.clone());
+                    }
+                }
+                Verdict::Playing => self.walk(&next, line),
+                Verdict::Failed => (),
+            }
+            line.pop();
+        }
+    }
+}
+
+// Counts the ways to solve a puzzle, stopping once limit of them are found. None when it has
+// no solution at all.
+pub fn rate(puzzle: &Puzzle, limit: usize) -> Option<Rating>{
+    rate_with(puzzle, limit, &mut Solver::new())
+}
+
+// The solver remembers positions for as long as the draws stay the same, so one that already
+// valued the puzzle saves most of the work.
+fn rate_with(puzzle: &Puzzle, limit: usize, solver: &mut Solver) -> Option<Rating>{
+    let mut count = Count{ puzzle, solver, limit, solutions: 0, shortest: None };
+    count.walk(&puzzle.game(), &mut Vec::new());
+    let solution = count.shortest?;
+    let mut game = puzzle.game();
+    let mut offered = 0;
+    for mv in solution.iter(){
+        offered += game.distinct_moves().len();
+        game.apply(*mv).expect("the solution was played on this puzzle");
+    }
+    Some(Rating{ solutions: count.solutions, branching: offered as f32 / solution.len() as f32, solution })
+}
+
+fn candidate(rng: &mut ChaCha8Rng, difficulty: Difficulty) -> Puzzle{
+    let (fewest, most) = difficulty.moves();
+    let moves = rng.gen_range(fewest..=most);
+    let clamp = if rng.gen_bool(0.25) { rng.gen_range(3..MAX_CARD) } else { MAX_CARD };
+    let mut polarity = [0; WORLDS];
+    for world in polarity.iter_mut(){
+        *world = loop{ // nothing starts balanced, or a claim would be free
+            let value = rng.gen_range(-clamp..=clamp);
+            if value != 0{
+                break value;
+            }
+        };
+    }
+    Puzzle{
+        name: String::new(),
+        description: String::new(),
+        polarity,
+        draws: (0..HAND_SIZE + moves).map(|_| rng.gen_range(1..=MAX_CARD)).collect(),
+        pleroma: rng.gen_bool(0.5),
+        clamp,
+        goal: Goal::Score{ worlds: 0, turns: None },
+    }
+}
+
+// A puzzle at the difficulty asked for, unique when one of the candidates tried is and
+// otherwise the one with the fewest solutions. Always solvable; the same seed always makes the
+// same puzzle, and None means no candidate fitted the difficulty at all.
+pub fn generate(difficulty: Difficulty, seed: u64) -> Option<(Puzzle, Rating)>{
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut best: Option<(Puzzle, Rating)> = None;
+    let mut solver = Solver::new();
+    for _ in 0..ATTEMPTS{
+        let mut puzzle = candidate(&mut rng, difficulty);
+        let worlds = solver.value(&puzzle.game());
+        if worlds == 0 || (worlds < 2 && difficulty != Difficulty::Easy){ // a single world is rarely much of a puzzle
+            continue;
+        }
+        puzzle.goal = Goal::Score{ worlds, turns: None };
+        let Some(rating) = rate_with(&puzzle, SOLUTION_LIMIT, &mut solver) else { continue };
+        // The draws past the shortest solution only make room for longer ones, so they go.
+        puzzle.draws.truncate(HAND_SIZE + rating.solution.len());
+        let Some(rating) = rate_with(&puzzle, SOLUTION_LIMIT, &mut solver) else { continue };
+        if !difficulty.fits(&rating) || best.as_ref().is_some_and(|(_, best)| best.solutions <= rating.solutions){
+            continue;
+        }
+        let unique = rating.solutions == 1;
+        best = Some((puzzle, rating));
+        if unique{
+            break;
+        }
+    }
+    let (mut puzzle, rating) = best?;
+    puzzle.name = format!("{} {seed:x}", difficulty.name());
+    Some((puzzle, rating))
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn generated_puzzles_keep_their_promises(){
+        for difficulty in Difficulty::ALL{
+            for seed in 0..3{
+                let (puzzle, rating) = generate(difficulty, seed).expect("every difficulty finds a puzzle");
+                assert_eq!(generate(difficulty, seed), Some((puzzle.clone(), rating.clone())));
+                assert_eq!(puzzle.check(), Ok(()));
+                assert_eq!(rate(&puzzle, SOLUTION_LIMIT).unwrap().solutions, rating.solutions);
+                assert!(difficulty.fits(&rating), "{} does not fit {}", puzzle.name, difficulty.name());
+                let mut game = puzzle.game();
+                let verdicts: Vec<Verdict> = rating.solution.iter().map(|mv| {
+                    let outcome = game.apply(*mv).unwrap();
+                    puzzle.verdict(&game, &outcome)
+                }).collect();
+                assert_eq!(verdicts.last(), Some(&Verdict::Solved), "{} is not solved by its solution", puzzle.name);
+                assert!(verdicts[..verdicts.len() - 1].iter().all(|verdict| *verdict == Verdict::Playing));
+            }
+        }
+    }
+}
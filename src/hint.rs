@@ -0,0 +1,60 @@
+use bevy::{prelude::*, tasks::{AsyncComputeTaskPool, Task, futures_lite::future}};
+use pleromakenoma::{mcts::{Budget, Mcts}, notation::Notation, rules::{Game, Move}};
+use crate::{input::{Bindings, InputAction}, session::Session, Card, GameSettings, WORLD_PHASE};
+
+// Iteration budgets offered in the settings, weakest first. 0 turns hints off.
+pub const HINT_STRENGTHS: [(u32, &str); 4] = [(0, "Off"), (200, "Weak"), (1000, "Normal"), (5000, "Strong")];
+
+pub fn hint_strength_name(iterations: u32) -> &'static str{
+    HINT_STRENGTHS.iter().find(|(strength, _)| *strength == iterations).map_or("Custom", |(_, name)| name)
+}
+
+// A search running off the main thread, as a Strong hint takes long enough to stall the board.
+#[derive(Resource, Default)]
+pub struct HintSearch{
+    task: Option<Task<Option<Move>>>,
+    asked: Option<Game>, // the position it was asked about, the answer is dropped once play moves on
+}
+
+// Asks the search for a move. Once a card is raised, only moves with that card are considered.
+pub fn show_hint(
+    input: Res<Input<KeyCode>>,
+    bindings: Res<Bindings>,
+    settings: Res<GameSettings>,
+    session: Res<Session>,
+    mut search: ResMut<HintSearch>,
+    query_cards: Query<&Card>,
+){
+    let phase = unsafe { WORLD_PHASE };
+    if !bindings.just_released(&input, InputAction::Hint) || settings.hint_strength == 0 || !matches!(phase, 0 | 3)
+        || session.game.mode.is_versus(){ // the search plays for the total, not for one side
+        return;
+    }
+    let active = query_cards.iter().find(|card| card.active).filter(|_| phase == 3).map(|card| (card.value, card.position));
+    let mut mcts = Mcts::new(Budget::iterations(settings.hint_strength), rand::random());
+    let game = session.game.clone();
+    search.asked = Some(game.clone());
+    // Replacing a task does not stop it, but the search is bounded by its iterations anyway.
+    search.task = Some(AsyncComputeTaskPool::get().spawn(async move {
+        let best = mcts.evaluate(&game).into_iter()
+            .map(|(mv, _, _)| mv)
+            .find(|mv| active.is_none_or(|(value, _)| game.hand()[mv.slot() as usize] == value))?;
+        Some(match (active, best){ // the same card value may have been searched from another slot
+            (Some((_, slot)), Move::Push{ world, .. }) => Move::Push{ slot, world },
+            (Some((_, slot)), Move::Claim{ .. }) => Move::Claim{ slot },
+            (None, mv) => mv,
+        })
+    }));
+}
+
+// Shows the search's move once it has one, if the game is still where it was asked.
+pub fn poll_hint(mut search: ResMut<HintSearch>, mut session: ResMut<Session>){
+    let Some(task) = search.task.as_mut() else { return };
+    let Some(best) = future::block_on(future::poll_once(task)) else { return };
+    search.task = None;
+    let asked = search.asked.take();
+    let Some(mv) = best.filter(|_| asked.as_ref() == Some(&session.game)) else { return };
+    let game = &session.game;
+    let card = game.hand()[mv.slot() as usize];
+    session.hint = Some(Notation{ pleroma: game.pleroma, mv, card: Some(card), result: None });
+}
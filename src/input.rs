@@ -0,0 +1,316 @@
+use std::collections::VecDeque;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::{gamepad::GamepadCursor, storage, FifthMarker, GameSettings, TextLabel, WORLD_PHASE};
+
+const BINDINGS_FILE: &str = "bindings.json";
+const INPUT_QUEUE_LEN: usize = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum InputAction{
+    SelectSlot(u8),
+    TargetWorld(u8),
+    Claim,
+    SlowerAnimations,
+    FasterAnimations,
+    ToggleInstantAnimations,
+    ToggleReducedMotion,
+    ToggleMoveLog,
+    Hint,
+    Pause,
+}
+
+impl InputAction{
+    fn is_move(self) -> bool{
+        matches!(self, InputAction::SelectSlot(_) | InputAction::TargetWorld(_) | InputAction::Claim)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum KeyPreset{
+    #[default]
+    Qwerty,
+    Azerty, // the letter row, since the unshifted number row of AZERTY boards types symbols
+    Numpad,
+}
+
+impl KeyPreset{
+    pub fn next(self) -> Self{
+        match self{
+            KeyPreset::Qwerty => KeyPreset::Azerty,
+            KeyPreset::Azerty => KeyPreset::Numpad,
+            KeyPreset::Numpad => KeyPreset::Qwerty,
+        }
+    }
+
+    fn bindings(self) -> Vec<Binding>{
+        let (slots, claim) = match self{
+            KeyPreset::Qwerty => ([KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4], KeyCode::Key5),
+            KeyPreset::Azerty => ([KeyCode::A, KeyCode::Z, KeyCode::E, KeyCode::R], KeyCode::T),
+            KeyPreset::Numpad => ([KeyCode::Numpad1, KeyCode::Numpad2, KeyCode::Numpad3, KeyCode::Numpad4], KeyCode::Numpad5),
+        };
+        let mut bindings = Vec::new();
+        for (i, key) in slots.into_iter().enumerate(){
+            bindings.push(Binding{ action: InputAction::SelectSlot(i as u8), keys: vec![key] });
+            bindings.push(Binding{ action: InputAction::TargetWorld(i as u8), keys: vec![key] });
+        }
+        bindings.push(Binding{ action: InputAction::Claim, keys: vec![claim] });
+        bindings.push(Binding{ action: InputAction::SlowerAnimations, keys: vec![KeyCode::Minus, KeyCode::NumpadSubtract] });
+        bindings.push(Binding{ action: InputAction::FasterAnimations, keys: vec![KeyCode::Equals, KeyCode::NumpadAdd] });
+        bindings.push(Binding{ action: InputAction::ToggleInstantAnimations, keys: vec![KeyCode::I] });
+        bindings.push(Binding{ action: InputAction::ToggleReducedMotion, keys: vec![KeyCode::M] });
+        bindings.push(Binding{ action: InputAction::ToggleMoveLog, keys: vec![KeyCode::L] });
+        bindings.push(Binding{ action: InputAction::Hint, keys: vec![KeyCode::H] });
+        bindings.push(Binding{ action: InputAction::Pause, keys: vec![KeyCode::Escape] });
+        bindings
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Binding{
+    pub action: InputAction,
+    pub keys: Vec<KeyCode>,
+}
+
+// Loaded from bindings.json. Leaving "bindings" empty in the file picks up the preset's keys,
+// as do actions added to the game after the file was written.
+#[derive(Resource, Serialize, Deserialize)]
+pub struct Bindings{
+    #[serde(default)]
+    pub preset: KeyPreset,
+    #[serde(default)]
+    pub bindings: Vec<Binding>,
+}
+
+impl Default for Bindings{
+    fn default() -> Self{
+        Bindings::from_preset(KeyPreset::default())
+    }
+}
+
+impl Bindings{
+    pub fn from_preset(preset: KeyPreset) -> Self{
+        Bindings{
+            preset,
+            bindings: preset.bindings(),
+        }
+    }
+
+    // Moves the keys over to another preset. Bindings edited by hand in bindings.json no longer
+    // match the old preset's and are left alone.
+    pub fn switch_preset(&mut self, preset: KeyPreset){
+        let (old, new) = (self.preset.bindings(), preset.bindings());
+        for binding in self.bindings.iter_mut(){
+            let untouched = old.iter().any(|default| default.action == binding.action && default.keys == binding.keys);
+            if let Some(default) = new.iter().find(|default| untouched && default.action == binding.action){
+                binding.keys = default.keys.clone();
+            }
+        }
+        for binding in new{
+            if !self.bindings.iter().any(|bound| bound.action == binding.action){
+                self.bindings.push(binding);
+            }
+        }
+        self.preset = preset;
+    }
+
+    pub fn load() -> Self{
+        let mut bindings = storage::load::<Bindings>(BINDINGS_FILE).unwrap_or_default();
+        for binding in bindings.preset.bindings(){
+            if !bindings.bindings.iter().any(|bound| bound.action == binding.action){
+                bindings.bindings.push(binding);
+            }
+        }
+        bindings.save(); // so there is always a file to edit
+        bindings
+    }
+
+    pub fn save(&self){
+        storage::save(BINDINGS_FILE, self);
+    }
+
+    pub fn keys(&self, action: InputAction) -> impl Iterator<Item = KeyCode> + '_{
+        self.bindings.iter()
+            .filter(move |binding| binding.action == action)
+            .flat_map(|binding| binding.keys.iter().copied())
+    }
+
+    pub fn just_pressed(&self, input: &Input<KeyCode>, action: InputAction) -> bool{
+        self.keys(action).any(|key| input.just_pressed(key))
+    }
+
+    pub fn just_released(&self, input: &Input<KeyCode>, action: InputAction) -> bool{
+        self.keys(action).any(|key| input.just_released(key))
+    }
+
+    pub fn label(&self, action: InputAction) -> String{
+        self.keys(action).next().map(key_name).unwrap_or_default()
+    }
+}
+
+pub fn key_name(key: KeyCode) -> String{
+    let name = format!("{key:?}");
+    if let Some(digit) = name.strip_prefix("Key"){
+        return digit.to_owned();
+    }
+    if let Some(rest) = name.strip_prefix("Numpad"){
+        return format!("Num{rest}");
+    }
+    name
+}
+
+#[derive(Resource, Default)]
+pub struct InputQueue{
+    presses: VecDeque<Vec<InputAction>>, // every move each buffered key press could stand for
+}
+
+impl InputQueue{
+    pub fn push(&mut self, settings: &GameSettings, press: Vec<InputAction>){
+        self.push_in_phase(unsafe { WORLD_PHASE }, settings.input_buffering, press);
+    }
+
+    // The board waits for a card in phase 0 and for a world in phase 3. Anything pressed in
+    // between is an animation still playing, and only kept with buffering on.
+    fn push_in_phase(&mut self, phase: i8, buffering: bool, press: Vec<InputAction>){
+        let ready = phase == 0 || phase == 3;
+        if phase == -99 || (!ready && !buffering){
+            return;
+        }
+        if !press.is_empty() && self.presses.len() < INPUT_QUEUE_LEN{
+            self.presses.push_back(press);
+        }
+    }
+
+    pub fn clear(&mut self){
+        self.presses.clear();
+    }
+
+    pub fn next(&mut self, accepts: impl Fn(InputAction) -> bool) -> Option<InputAction>{
+        while let Some(press) = self.presses.pop_front(){
+            if let Some(action) = press.into_iter().find(|action| accepts(*action)){
+                return Some(action);
+            }
+        }
+        None
+    }
+}
+
+pub fn queue_input(
+    input: Res<Input<KeyCode>>,
+    bindings: Res<Bindings>,
+    settings: Res<GameSettings>,
+    mut queue: ResMut<InputQueue>,
+){
+    if unsafe { WORLD_PHASE } == -99{
+        queue.presses.clear();
+        return;
+    }
+    for key in input.get_just_released(){
+        let press: Vec<InputAction> = bindings.bindings.iter()
+            .filter(|binding| binding.action.is_move() && binding.keys.contains(key))
+            .map(|binding| binding.action)
+            .collect();
+        queue.push(&settings, press);
+    }
+}
+
+#[derive(Component)]
+pub struct KeyHint{ // names the key bound to a TextLabel, or to the FifthMarker when number is 4
+    pub number: u8,
+}
+
+// The digits drawn on the TextLabel and FifthMarker sprites only name the Qwerty keys, so they
+// are hidden behind any hint and only show when nothing is bound.
+pub fn show_bound_keys(
+    bindings: Res<Bindings>,
+    cursor: Res<GamepadCursor>,
+    mut query: Query<(&KeyHint, &Parent, &mut Text)>,
+    mut query_glyphs: Query<&mut TextureAtlasSprite, Or<(With<TextLabel>, With<FifthMarker>)>>,
+){
+    let at_worlds = !matches!(unsafe { WORLD_PHASE }, 0 | -4 | 6);
+    for (hint, parent, mut text) in query.iter_mut(){
+        let action = match hint.number{
+            4 => InputAction::Claim,
+            n if at_worlds => InputAction::TargetWorld(n),
+            n => InputAction::SelectSlot(n),
+        };
+        let label = if cursor.active{
+            cursor.button_label(action)
+        } else {
+            bindings.label(action)
+        };
+        let glyph_size = if label.is_empty() { Vec2::new(16., 16.) } else { Vec2::ZERO };
+        if let Ok(mut glyph) = query_glyphs.get_mut(parent.get()){
+            if glyph.custom_size != Some(glyph_size){
+                glyph.custom_size = Some(glyph_size); // not the alpha, which DissolveLens animates
+            }
+        }
+        if text.sections[0].value != label{
+            text.sections[0].value = label;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    // Key 1 under the Qwerty preset, which picks a card or a world depending on the phase.
+    fn key_one() -> Vec<InputAction>{
+        vec![InputAction::SelectSlot(0), InputAction::TargetWorld(0)]
+    }
+
+    #[test]
+    fn switching_presets_keeps_hand_edited_keys(){
+        let mut bindings = Bindings::from_preset(KeyPreset::Qwerty);
+        for binding in bindings.bindings.iter_mut().filter(|binding| binding.action == InputAction::Hint){
+            binding.keys = vec![KeyCode::F1];
+        }
+        bindings.switch_preset(KeyPreset::Azerty);
+        assert_eq!(bindings.preset, KeyPreset::Azerty);
+        assert_eq!(bindings.keys(InputAction::Hint).collect::<Vec<_>>(), vec![KeyCode::F1]);
+        assert_eq!(bindings.keys(InputAction::SelectSlot(1)).collect::<Vec<_>>(), vec![KeyCode::Z]);
+        assert_eq!(bindings.keys(InputAction::Claim).collect::<Vec<_>>(), vec![KeyCode::T]);
+    }
+
+    #[test]
+    fn buffers_up_to_the_limit_during_animations(){
+        let mut queue = InputQueue::default();
+        for slot in 0..4{
+            queue.push_in_phase(1, true, vec![InputAction::SelectSlot(slot)]);
+        }
+        assert_eq!(queue.presses.len(), INPUT_QUEUE_LEN);
+        assert_eq!(queue.next(|_| true), Some(InputAction::SelectSlot(0)));
+        assert_eq!(queue.next(|_| true), Some(InputAction::SelectSlot(1)));
+        assert_eq!(queue.next(|_| true), None);
+    }
+
+    #[test]
+    fn drops_presses_during_animations_without_buffering(){
+        let mut queue = InputQueue::default();
+        queue.push_in_phase(1, false, key_one());
+        assert!(queue.presses.is_empty());
+        queue.push_in_phase(0, false, key_one());
+        queue.push_in_phase(3, false, key_one());
+        assert_eq!(queue.presses.len(), 2);
+    }
+
+    #[test]
+    fn drops_presses_once_the_game_is_over(){
+        let mut queue = InputQueue::default();
+        queue.push_in_phase(-99, true, key_one());
+        queue.push_in_phase(0, true, Vec::new());
+        assert!(queue.presses.is_empty());
+    }
+
+    #[test]
+    fn next_takes_the_first_action_the_board_accepts(){
+        let mut queue = InputQueue::default();
+        queue.push_in_phase(0, true, vec![InputAction::Claim]);
+        queue.push_in_phase(0, true, key_one());
+        let at_worlds = |action| matches!(action, InputAction::TargetWorld(_));
+        assert_eq!(queue.next(at_worlds), Some(InputAction::TargetWorld(0)));
+        assert!(queue.presses.is_empty(), "the claim nobody accepted is dropped on the way");
+        assert_eq!(queue.next(at_worlds), None);
+    }
+}
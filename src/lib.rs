@@ -0,0 +1,15 @@
+// The rules of Pleroma & Kenoma without any rendering, shared by the game and headless tools.
+
+pub mod bot;
+pub mod code;
+pub mod env;
+pub mod ffi;
+pub mod generator;
+pub mod mcts;
+pub mod net;
+pub mod notation;
+pub mod protocol;
+pub mod puzzle;
+pub mod rules;
+pub mod solver;
+pub mod spectate;
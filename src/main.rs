@@ -1,11 +1,57 @@
 use std::{f32::consts::PI, time::Duration};
-use bevy::{prelude::*, render::camera::Viewport, core_pipeline::clear_color::ClearColorConfig};
+use bevy::{prelude::*, render::{camera::Viewport, view::RenderLayers}, core_pipeline::clear_color::ClearColorConfig};
 use bevy_tweening::{*, lens::TransformPositionLens};
-use rand::prelude::*;
+use achievements::Achievements;
+use daily::DailyRecord;
+use serde::{Deserialize, Serialize};
+use gamepad::GamepadCursor;
+use hint::HINT_STRENGTHS;
+use input::{Bindings, InputAction, InputQueue, KeyHint};
+use menu::{AppState, MenuPlugin};
+use pleromakenoma::{generator::Difficulty, rules::{Mode, Move}};
+use scores::Leaderboard;
+use session::{NextGame, Session};
+use stats::LifetimeStats;
+
+mod achievements;
+mod analysis;
+mod daily;
+mod date;
+mod gamepad;
+mod hint;
+mod input;
+mod menu;
+mod move_log;
+mod network;
+mod puzzles;
+mod scores;
+mod session;
+mod stats;
+mod storage;
+mod stream;
+mod versus;
+
+const SETTINGS_FILE: &str = "settings.json";
 
 fn main() {
+    let mut settings = GameSettings::load();
+    // --profile NAME plays as that profile from now on, starting it if it is new.
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next(){
+        if arg != "--profile"{
+            continue;
+        }
+        match args.next(){
+            Some(name) if storage::is_profile_name(&name) => {
+                settings.profile = name;
+                settings.save();
+            }
+            _ => eprintln!("--profile takes a name of up to 24 letters, digits, - and _"),
+        }
+    }
     App::new()
         .add_plugins(bevy_tweening::TweeningPlugin)
+        .add_systems(Update, component_animator_system::<TextureAtlasSprite>)
         .add_plugins(
             DefaultPlugins
                 .set(ImagePlugin::default_nearest())
@@ -20,15 +66,53 @@ fn main() {
                 })
                 .build(),
         )
+        .add_plugins(MenuPlugin)
+        .add_asset::<puzzles::PackAsset>()
+        .init_asset_loader::<puzzles::PackLoader>()
+        .init_resource::<puzzles::Puzzles>()
         .insert_resource(ClearColor(Color::rgb(0.0, 0.0, 0.0)))
-        .add_systems(Startup, setup)
-        .add_systems(Startup, distribute_starting_cards)
-        .add_systems(Update, select_card)
-        .add_systems(Update, move_text_labels)
-        .add_systems(Update, push_world_polarity)
-        .add_systems(Update, banish_and_replace)
-        .add_systems(Update, claim_balanced_worlds)
-        .add_systems(Update, swap_pleroma_kenoma)
+        .insert_resource(LifetimeStats::load(&settings.profile))
+        .insert_resource(Achievements::load(&settings.profile))
+        .insert_resource(settings)
+        .init_resource::<InputQueue>()
+        .insert_resource(Bindings::load())
+        .init_resource::<GamepadCursor>()
+        .init_resource::<Session>()
+        .init_resource::<NextGame>()
+        .init_resource::<hint::HintSearch>()
+        .init_resource::<network::Network>()
+        .init_resource::<stream::Stream>()
+        .insert_resource(Leaderboard::load())
+        .insert_resource(DailyRecord::load())
+        .add_systems(Startup, (setup_cameras, achievements::spawn_toast_camera))
+        .add_systems(Startup, gamepad::spawn_cursor_highlight)
+        .add_systems(OnEnter(AppState::Dealing), (session::new_session, network::attach_lockstep, stream::note_deal, clear_board, setup, distribute_starting_cards, move_log::spawn_move_log,
+            versus::spawn_versus_panel, network::spawn_network_status, start_playing).chain())
+        .add_systems(Update, gamepad::move_cursor_highlight)
+        .add_systems(Update, input::show_bound_keys)
+        .add_systems(Update, move_log::update_move_log)
+        .add_systems(Update, versus::update_turn_indicator)
+        .add_systems(Update, stream::publish_game)
+        .add_systems(Update, network::follow_stream.after(input::queue_input).after(gamepad::gamepad_input).before(session::play_replay)
+            .run_if(in_state(AppState::Playing).or_else(in_state(AppState::GameOver))))
+        .add_systems(Update, (achievements::check_achievements, achievements::show_toasts).chain())
+        .add_systems(Update, (
+            input::queue_input.before(select_card).before(push_world_polarity),
+            gamepad::gamepad_input.before(select_card).before(push_world_polarity),
+            network::sync_lockstep.after(input::queue_input).after(gamepad::gamepad_input).before(session::play_replay),
+            session::play_replay.before(select_card).before(push_world_polarity),
+            select_card,
+            adjust_motion_settings,
+            move_log::toggle_move_log,
+            hint::show_hint,
+            hint::poll_hint,
+            move_text_labels,
+            push_world_polarity,
+            banish_and_replace,
+            claim_balanced_worlds,
+            swap_pleroma_kenoma,
+            session::finish_game,
+        ).run_if(in_state(AppState::Playing)))
         .run();
 }
 
@@ -73,7 +157,8 @@ struct Deck{
 
 #[derive(Component)]
 struct BalancedWorlds{
-    capacity: u16
+    capacity: u16,
+    player: Option<u8>, // counts one player's claims in versus, or everyone's when None
 }
 
 #[derive(Component)]
@@ -87,12 +172,200 @@ struct Dimension{
     pleroma: bool,
 }
 
+#[derive(Component)]
+struct Board{} // everything despawned when a new game is dealt
+
+#[derive(Resource, Serialize, Deserialize)]
+#[serde(default)]
+struct GameSettings{
+    input_buffering: bool, // keep key presses made during animations instead of dropping them
+    animation_speed: f32, // tween durations are divided by this
+    instant_animations: bool,
+    // No overshoot, and long slides become a quick fade out and back in. None follows the OS or
+    // browser preference, which is asked again on every start as it may have changed since.
+    reduced_motion_choice: Option<bool>,
+    #[serde(skip)]
+    prefers_reduced_motion: bool,
+    show_move_log: bool,
+    hint_strength: u32, // search iterations spent on a hint
+    spectator_stream: bool, // publish the game for spectators, see stream.rs
+    puzzle_difficulty: Difficulty, // of fresh puzzles, see puzzles.rs
+    profile: String, // whose statistics and achievements these games count for, see storage.rs
+}
+
+impl GameSettings{
+    fn load() -> Self{
+        let mut settings: GameSettings = storage::load(SETTINGS_FILE).unwrap_or_default();
+        if !storage::is_profile_name(&settings.profile){ // it becomes part of a path
+            settings.profile = storage::DEFAULT_PROFILE.to_owned();
+        }
+        // A hand-edited file could stall every tween or the search, so only offered values load.
+        settings.set_animation_speed(settings.animation_speed);
+        settings.prefers_reduced_motion = prefers_reduced_motion();
+        settings.hint_strength = HINT_STRENGTHS.iter().rev().map(|(strength, _)| *strength)
+            .find(|strength| *strength <= settings.hint_strength).unwrap_or(0);
+        settings
+    }
+
+    fn save(&self){
+        storage::save(SETTINGS_FILE, self);
+    }
+
+    fn reduced_motion(&self) -> bool{
+        self.reduced_motion_choice.unwrap_or(self.prefers_reduced_motion)
+    }
+
+    // Following the system, then the other way from it, then the same way, then following again.
+    fn cycle_reduced_motion(&mut self){
+        self.reduced_motion_choice = match self.reduced_motion_choice{
+            None => Some(!self.prefers_reduced_motion),
+            Some(choice) if choice != self.prefers_reduced_motion => Some(self.prefers_reduced_motion),
+            Some(_) => None,
+        };
+    }
+
+    fn set_animation_speed(&mut self, speed: f32){
+        self.animation_speed = if speed.is_finite() { speed.clamp(0.25, 4.) } else { 1. }; // clamp keeps NaN
+    }
+}
+
+impl Default for GameSettings{
+    fn default() -> Self{
+        GameSettings{
+            input_buffering: true,
+            animation_speed: 1.,
+            instant_animations: false,
+            reduced_motion_choice: None,
+            prefers_reduced_motion: false, // asked in load
+            show_move_log: true,
+            hint_strength: 1000,
+            spectator_stream: false,
+            puzzle_difficulty: Difficulty::default(),
+            profile: storage::DEFAULT_PROFILE.to_owned(),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn prefers_reduced_motion() -> bool{
+    web_sys::window()
+        .and_then(|window| window.match_media("(prefers-reduced-motion: reduce)").ok().flatten())
+        .map(|query| query.matches())
+        .unwrap_or(false)
+}
+
+// Asks the desktop's own settings tool, as there is no portable way to ask. Anything that goes
+// wrong, such as the tool not being there, counts as no preference.
+#[cfg(not(target_arch = "wasm32"))]
+fn prefers_reduced_motion() -> bool{
+    let ask = |program: &str, args: &[&str]| std::process::Command::new(program).args(args).output().ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned());
+    if cfg!(target_os = "macos"){
+        ask("defaults", &["read", "com.apple.universalaccess", "reduceMotion"]).is_some_and(|value| value == "1")
+    } else if cfg!(target_os = "windows"){ // the "Animate controls and elements" setting
+        ask("reg", &["query", r"HKCU\Control Panel\Desktop\WindowMetrics", "/v", "MinAnimate"]).is_some_and(|value| value.ends_with(" 0"))
+    } else {
+        ask("gsettings", &["get", "org.gnome.desktop.interface", "enable-animations"]).is_some_and(|value| value == "false")
+    }
+}
+
+const REDUCED_MOTION_MAX_SLIDE: f32 = 150.;
+
+struct JumpLens{ // stays at start for the first half, then sits at end
+    start: Vec3,
+    end: Vec3,
+}
+
+impl Lens<Transform> for JumpLens{
+    fn lerp(&mut self, target: &mut Transform, ratio: f32){
+        target.translation = if ratio < 0.5 { self.start } else { self.end };
+    }
+}
+
+struct DissolveLens{} // fades out until the halfway point, then back in
+
+impl Lens<TextureAtlasSprite> for DissolveLens{
+    fn lerp(&mut self, target: &mut TextureAtlasSprite, ratio: f32){
+        target.color.set_a((1. - 2. * ratio).abs());
+    }
+}
+
 static mut WORLD_PHASE: i8 = 0;
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut texture_atlases: ResMut<Assets<TextureAtlas>>) {
+fn clear_board(mut commands: Commands, query: Query<Entity, With<Board>>, mut queue: ResMut<InputQueue>){
+    for entity in query.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+    *queue = InputQueue::default();
+    world_phase_update(0);
+}
+
+fn start_playing(mut next_state: ResMut<NextState<AppState>>){
+    next_state.set(AppState::Playing);
+}
+
+fn setup_cameras(mut commands: Commands){
+    commands.spawn((
+        Camera2dBundle {
+            transform: Transform{
+                translation: Vec3 { x: -1152./4., y: 0., z: 0. },
+                ..default()
+            },
+            camera: Camera{
+                order: 0,
+                viewport: Some(Viewport {
+                    physical_position: UVec2::new(0, 0),
+                    physical_size: UVec2::new(1152/2, 648),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..default()
+        },
+        UiCameraConfig{ show_ui: false },
+    ));
+    commands.spawn((
+        Camera2dBundle {
+            transform: Transform{
+                translation: Vec3 { x: 1152./4.+119., y: -1500., z: 0. },
+                ..default()
+            },
+            camera: Camera{
+                order: 1,
+                viewport: Some(Viewport {
+                    physical_position: UVec2::new(1152/2, 0),
+                    physical_size: UVec2::new(1152/2, 648),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            camera_2d: Camera2d{
+                clear_color: ClearColorConfig::None,
+            },
+            ..default()
+        },
+        UiCameraConfig{ show_ui: false },
+    ));
+    commands.spawn(( // menus, drawn over both halves of the window
+        Camera2dBundle {
+            camera: Camera{
+                order: 2,
+                ..Default::default()
+            },
+            camera_2d: Camera2d{
+                clear_color: ClearColorConfig::None,
+            },
+            ..default()
+        },
+        RenderLayers::layer(1),
+    ));
+}
+
+fn setup(mut commands: Commands, session: Res<Session>, asset_server: Res<AssetServer>, mut texture_atlases: ResMut<Assets<TextureAtlas>>) {
     // Rectangle
-    commands.spawn(WorldManager{kenoma: true});
-    commands.spawn(SpriteBundle {
+    commands.spawn((WorldManager{kenoma: !session.game.pleroma}, Board{}));
+    commands.spawn((SpriteBundle {
         sprite: Sprite {
             color: Color::rgb(1.0, 1.0, 1.0),
             custom_size: Some(Vec2::new(1152.0/2.0+150., 648.0)),
@@ -100,8 +373,10 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut texture_atl
         },
         transform: Transform::from_translation(Vec3::new(350.0, -1500.0, 0.)),
         ..default()
-    });
-    commands.spawn(SpriteBundle {
+    },
+    Board{},
+    ));
+    commands.spawn((SpriteBundle {
         sprite: Sprite {
             color: Color::rgb(1.0, 1.0, 1.0),
             custom_size: Some(Vec2::new(1152.0, 5.0)),
@@ -109,8 +384,10 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut texture_atl
         },
         transform: Transform::from_translation(Vec3::new(-50.0, -180.0, 0.)),
         ..default()
-    });
-    commands.spawn(SpriteBundle {
+    },
+    Board{},
+    ));
+    commands.spawn((SpriteBundle {
         sprite: Sprite {
             color: Color::rgb(0.0, 0.0, 0.0),
             custom_size: Some(Vec2::new(1152.0, 5.0)),
@@ -118,7 +395,9 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut texture_atl
         },
         transform: Transform::from_translation(Vec3::new(500.0, -1680.0, 0.)),
         ..default()
-    });
+    },
+    Board{},
+    ));
     let img_path = "spritesheet.png".to_owned();
     let texture_handle = asset_server.load(&img_path);
     let texture_atlas = TextureAtlas::from_grid(
@@ -131,7 +410,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut texture_atl
         let y_c = -100.0+(i as f32/7.0).floor()*120.0;
         let x_c = -590.0+(i as f32%7.0)*80.0;
         let tex = 6.0-(i as f32%7.0);
-        commands.spawn(SpriteSheetBundle {
+        commands.spawn((SpriteSheetBundle {
             texture_atlas: texture_atlas_handle.clone(),
             sprite: TextureAtlasSprite{
                 index : tex as usize,
@@ -143,13 +422,15 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut texture_atl
                 ..default()
             },
             ..default()
-        });
+        },
+        Board{},
+        ));
     }
     for i in 0..25{ // black markers
         let y_c = -1600.0+(i as f32/6.0).floor()*120.0;
         let x_c = 230.0+(i as f32%6.0)*80.0;
         let tex = i as f32%6.0;
-        commands.spawn(SpriteSheetBundle {
+        commands.spawn((SpriteSheetBundle {
             texture_atlas: texture_atlas_handle.clone(),
             sprite: TextureAtlasSprite{
                 index : tex as usize,
@@ -162,15 +443,16 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut texture_atl
                 ..default()
             },
             ..default()
-        });
+        },
+        Board{},
+        ));
     }
     for i in 0..4{ // white
-        let options = [-4,-3,-2,2,3,4];
-        let starting_offset: Vec<_> = options
-        .choose_multiple(&mut rand::thread_rng(), 1)
-        .collect();
-        let starting_offset = *starting_offset[0];
-        let end_x : f32 = if starting_offset > 0{
+        let starting_offset = session.game.polarity[i as usize];
+        let end_x : f32 = if starting_offset == 0{ // only puzzles start on a balanced world
+            0.
+        }
+        else if starting_offset > 0{
             190. + (starting_offset-2) as f32*80.
         }
         else {
@@ -194,7 +476,8 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut texture_atl
         Dimension{
             world: i,
             pleroma: false
-        }
+        },
+        Board{},
         ));
         commands.spawn((SpriteSheetBundle {
             texture_atlas: texture_atlas_handle.clone(),
@@ -215,7 +498,8 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut texture_atl
             polarity: starting_offset,
             world: i,
             dimension: false,
-        }
+        },
+        Board{},
     ));
             commands.spawn((SpriteSheetBundle {
             texture_atlas: texture_atlas_handle.clone(),
@@ -235,7 +519,8 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut texture_atl
         Dimension{
             world: i,
             pleroma: true,
-        }
+        },
+        Board{},
         ));
         commands.spawn((SpriteSheetBundle {
             texture_atlas: texture_atlas_handle.clone(),
@@ -256,52 +541,13 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut texture_atl
             polarity: starting_offset,
             world: i,
             dimension: true,
-        }
-    ));
-    }
-    commands.spawn((
-        Camera2dBundle {
-            transform: Transform{
-                translation: Vec3 { x: -1152./4., y: 0., z: 0. },
-                ..default()
-            },
-            camera: Camera{
-                order: 0,
-                viewport: Some(Viewport {
-                    physical_position: UVec2::new(0, 0),
-                    physical_size: UVec2::new(1152/2, 648),
-                    ..Default::default()
-                }),
-                ..Default::default()
-            },
-            ..default()
-        },
-    ));
-    commands.spawn((
-        Camera2dBundle {
-            transform: Transform{
-                translation: Vec3 { x: 1152./4.+119., y: -1500., z: 0. },
-                ..default()
-            },
-            camera: Camera{
-                order: 1,
-                viewport: Some(Viewport {
-                    physical_position: UVec2::new(1152/2, 0),
-                    physical_size: UVec2::new(1152/2, 648),
-                    ..Default::default()
-                }),
-                ..Default::default()
-            },
-            camera_2d: Camera2d{
-                clear_color: ClearColorConfig::None,
-            },
-            ..default()
         },
+        Board{},
     ));
+    }
 }
 
-fn distribute_starting_cards(mut commands: Commands, asset_server: Res<AssetServer>, mut texture_atlases: ResMut<Assets<TextureAtlas>>){
-    let card_values = [rand::thread_rng().gen_range(1..7),rand::thread_rng().gen_range(1..7),rand::thread_rng().gen_range(1..7),rand::thread_rng().gen_range(1..7)];
+fn distribute_starting_cards(mut commands: Commands, session: Res<Session>, settings: Res<GameSettings>, asset_server: Res<AssetServer>, mut texture_atlases: ResMut<Assets<TextureAtlas>>){
     let img_path = "spritesheet.png".to_owned();
     let texture_handle = asset_server.load(&img_path);
     let texture_atlas = TextureAtlas::from_grid(
@@ -310,9 +556,17 @@ fn distribute_starting_cards(mut commands: Commands, asset_server: Res<AssetServ
         80, 2, None, None
     );
     let texture_atlas_handle = texture_atlases.add(texture_atlas);
+    let font = asset_server.load("Play-Regular.ttf");
+    let hint_style = TextStyle {
+        font: font.clone(),
+        font_size: 14.0,
+        color: Color::rgb(0.6, 0.6, 0.6),
+    };
+    // A puzzle can start in Pleroma, with everything already where swap_pleroma_kenoma leaves it.
+    let (shift_x, label_y) = if session.game.pleroma { (675., -1500.) } else { (0., 0.) };
     for i in 0..4_u8{
         let img_path = "spritesheet.png".to_owned();
-        let card_value = card_values[i as usize];
+        let card_value = session.game.hands[0][i as usize];
         let texture_handle = asset_server.load(&img_path);
         let texture_atlas = TextureAtlas::from_grid(
             texture_handle,
@@ -320,25 +574,15 @@ fn distribute_starting_cards(mut commands: Commands, asset_server: Res<AssetServ
             80, 2, None, None
         );
         let texture_atlas_handle = texture_atlases.add(texture_atlas);
-        let tween = Tween::new(
-            EaseFunction::QuadraticInOut,
-            Duration::from_secs(1),
-            TransformPositionLens {
-                start: Vec3 { x: -400.+80.*i as f32, y: -400., z: 0. },
-                end: Vec3::new(-400.+80.*i as f32, -250., 0.),
-            },
-        );
-        let tween_text = Tween::new(
-            // Use a quadratic easing on both endpoints.
-            EaseFunction::QuadraticInOut,
-            // Animation time.
-            Duration::from_secs(1),
-            TransformPositionLens {
-                start: Vec3 { x: -400.+80.*i as f32, y: -400., z: 0. },
-                end: Vec3::new(-400.+80.*i as f32, -300., 0.),
-            },
-        );
-        commands.spawn( // card hotkeys
+        let lens = TransformPositionLens {
+            start: Vec3 { x: shift_x-400.+80.*i as f32, y: -400., z: 0. },
+            end: Vec3::new(shift_x-400.+80.*i as f32, -250., 0.),
+        };
+        let lens_text = TransformPositionLens {
+            start: Vec3 { x: shift_x-400.+80.*i as f32, y: label_y-400., z: 0. },
+            end: Vec3::new(shift_x-400.+80.*i as f32, label_y-300., 0.),
+        };
+        let label = commands.spawn( // card hotkeys
             (
                 SpriteSheetBundle { // cards
                     texture_atlas: texture_atlas_handle.clone(),
@@ -350,15 +594,18 @@ fn distribute_starting_cards(mut commands: Commands, asset_server: Res<AssetServ
                     },
                     ..default()
                 },
-                Animator::new(tween_text),
                 TextLabel{
                     number: i,
                 },
                 Pleromic{ pleroma: false, dist: 0.},
-                Active{}
+                Active{},
+                Board{},
             )
-        );
-        commands.spawn((SpriteSheetBundle { // cards
+        ).with_children(|parent| {
+            parent.spawn(key_hint(&hint_style, i));
+        }).id();
+        move_entity(&mut commands, &settings, label, EaseFunction::QuadraticInOut, Duration::from_secs(1), lens_text, None);
+        let card = commands.spawn((SpriteSheetBundle { // cards
             texture_atlas: texture_atlas_handle.clone(),
             sprite: TextureAtlasSprite{
                 index : (card_value-1) as usize,
@@ -372,10 +619,11 @@ fn distribute_starting_cards(mut commands: Commands, asset_server: Res<AssetServ
             position: i,
             active: false,
         },
-        Animator::new(tween),
         Pleromic{ pleroma: false, dist: 675.},
-        Active{}
-        ));
+        Active{},
+        Board{},
+        )).id();
+        move_entity(&mut commands, &settings, card, EaseFunction::QuadraticInOut, Duration::from_secs(1), lens, None);
     }
 
     // WHITE
@@ -390,17 +638,13 @@ fn distribute_starting_cards(mut commands: Commands, asset_server: Res<AssetServ
             Vec2::new(16.0, 16.0),
             80, 2, None, None
         );
-        let card_value = card_values[i as usize];
+        let card_value = session.game.hands[1][i as usize];
         let texture_atlas_handle = texture_atlases.add(texture_atlas);
-        let tween = Tween::new(
-            EaseFunction::QuadraticInOut,
-            Duration::from_secs(1),
-            TransformPositionLens {
-                start: Vec3 { x: -400.+80.*i as f32, y: -1500.-400., z: 0. },
-                end: Vec3::new(-400.+80.*i as f32, -1500.-250., 0.),
-            },
-        );
-        commands.spawn((SpriteSheetBundle { // cards
+        let lens = TransformPositionLens {
+            start: Vec3 { x: shift_x-400.+80.*i as f32, y: -1500.-400., z: 0. },
+            end: Vec3::new(shift_x-400.+80.*i as f32, -1500.-250., 0.),
+        };
+        let card = commands.spawn((SpriteSheetBundle { // cards
             texture_atlas: texture_atlas_handle.clone(),
             sprite: TextureAtlasSprite{
                 index : (card_value-1) as usize,
@@ -415,9 +659,10 @@ fn distribute_starting_cards(mut commands: Commands, asset_server: Res<AssetServ
             position: i,
             active: false,
         },
-        Animator::new(tween),
         Pleromic{ pleroma: true, dist: 675.},
-        ));
+        Board{},
+        )).id();
+        move_entity(&mut commands, &settings, card, EaseFunction::QuadraticInOut, Duration::from_secs(1), lens, None);
     }
 
     // END
@@ -425,83 +670,74 @@ fn distribute_starting_cards(mut commands: Commands, asset_server: Res<AssetServ
     // 
 
 
-    let tween_deck = Tween::new(
-        // Use a quadratic easing on both endpoints.
-        EaseFunction::QuadraticInOut,
-        // Animation time.
-        Duration::from_secs(1),
-        TransformPositionLens {
-            start: Vec3 { x: -480., y: -400., z: 0. },
-            end: Vec3::new(-480., -218., 0.),
-        },
-    );
-    let tween_num_bal = Tween::new(
-        // Use a quadratic easing on both endpoints.
-        EaseFunction::QuadraticInOut,
-        // Animation time.
-        Duration::from_secs(1),
-        TransformPositionLens {
-            start: Vec3 { x: -480., y: -400., z: 0. },
-            end: Vec3::new(-480., -278., 0.),
-        },
-    );
-    let tween_bal = Tween::new(
-        // Use a quadratic easing on both endpoints.
-        EaseFunction::QuadraticInOut,
-        // Animation time.
-        Duration::from_secs(1),
-        TransformPositionLens {
-            start: Vec3 { x: -520., y: -400., z: 0. },
-            end: Vec3::new(-520., -280., 0.),
-        },
-    );
-    let tween = Tween::new(
-        // Use a quadratic easing on both endpoints.
-        EaseFunction::QuadraticInOut,
-        // Animation time.
-        Duration::from_secs(1),
-        TransformPositionLens {
-            start: Vec3 { x: -520., y: -400., z: 0. },
-            end: Vec3::new(-520., -220., 0.),
-        },
-    );
-    let font = asset_server.load("Play-Regular.ttf");
+    let lens_deck = TransformPositionLens {
+        start: Vec3 { x: -480., y: -400., z: 0. },
+        end: Vec3::new(-480., -218., 0.),
+    };
+    let lens_num_bal = TransformPositionLens {
+        start: Vec3 { x: -480., y: -400., z: 0. },
+        end: Vec3::new(-480., -278., 0.),
+    };
+    let lens_bal = TransformPositionLens {
+        start: Vec3 { x: -520., y: -400., z: 0. },
+        end: Vec3::new(-520., -280., 0.),
+    };
+    let lens = TransformPositionLens {
+        start: Vec3 { x: -520., y: -400., z: 0. },
+        end: Vec3::new(-520., -220., 0.),
+    };
     let text_style = TextStyle {
         font: font.clone(),
         font_size: 30.0,
         color: Color::WHITE,
     };
     let text_alignment = TextAlignment::Center;
-    commands.spawn( // deck counter
+    let deck = commands.spawn( // deck counter
         (
             Text2dBundle {
-                text: Text::from_section("21", text_style.clone())
+                text: Text::from_section(session.game.deck.len().to_string(), text_style.clone())
                 .with_alignment(text_alignment),
             ..default()
             },
-            Animator::new(tween_deck),
             Deck{
-                capacity: 21,
+                capacity: session.game.deck.len() as u16,
             },
             Active{},
+            Board{},
         )
-    );
-    commands.spawn( // world counter
+    ).id();
+    move_entity(&mut commands, &settings, deck, EaseFunction::QuadraticInOut, Duration::from_secs(1), lens_deck, None);
+    let balanced = commands.spawn( // world counter
         (
             Text2dBundle {
                 text: Text::from_section("0", text_style.clone())
                 .with_alignment(text_alignment),
             ..default()
             },
-            Animator::new(tween_num_bal),
             BalancedWorlds{
                 capacity: 0,
+                player: None,
             },
             Active{},
+            Board{},
         )
-    );
+    ).id();
+    move_entity(&mut commands, &settings, balanced, EaseFunction::QuadraticInOut, Duration::from_secs(1), lens_num_bal, None);
+    commands.spawn(( // seed and preset, so a good deal can be replayed
+        Text2dBundle {
+            text: Text::from_section(match (&session.puzzle, session.daily){
+                (Some(puzzle), _) => format!("{}: {}", puzzle.name, puzzle.goal.describe()),
+                (None, Some(day)) => format!("Daily {} {:08x}", date::format_date(day * 86_400), session.seed),
+                (None, None) => format!("{} {:08x}", session.preset.name(), session.seed),
+            }, hint_style.clone())
+            .with_alignment(text_alignment),
+            transform: Transform::from_xyz(-288., 312., 0.),
+            ..default()
+        },
+        Board{},
+    ));
 
-    commands.spawn((SpriteSheetBundle { // deck icon
+    let deck_icon = commands.spawn((SpriteSheetBundle { // deck icon
         texture_atlas: texture_atlas_handle.clone(),
         sprite: TextureAtlasSprite{
             index : 10_usize,
@@ -510,10 +746,11 @@ fn distribute_starting_cards(mut commands: Commands, asset_server: Res<AssetServ
         },
         ..default()
     },
-    Animator::new(tween),
     Active{},
-    ));
-    commands.spawn((SpriteSheetBundle { // world icon
+    Board{},
+    )).id();
+    move_entity(&mut commands, &settings, deck_icon, EaseFunction::QuadraticInOut, Duration::from_secs(1), lens, None);
+    let world_icon = commands.spawn((SpriteSheetBundle { // world icon
         texture_atlas: texture_atlas_handle.clone(),
         sprite: TextureAtlasSprite{
             index : 8_usize,
@@ -527,29 +764,18 @@ fn distribute_starting_cards(mut commands: Commands, asset_server: Res<AssetServ
         ..default()
     },
     Active{},
-    Animator::new(tween_bal),
-    ));
-    let tween_bal = Tween::new(
-        // Use a quadratic easing on both endpoints.
-        EaseFunction::QuadraticInOut,
-        // Animation time.
-        Duration::from_secs(1),
-        TransformPositionLens {
-            start: Vec3 { x: -520., y: -1500.-400., z: 0. },
-            end: Vec3::new(-520., -1500.-280., 0.),
-        },
-    );
-    let tween = Tween::new(
-        // Use a quadratic easing on both endpoints.
-        EaseFunction::QuadraticInOut,
-        // Animation time.
-        Duration::from_secs(1),
-        TransformPositionLens {
-            start: Vec3 { x: -520., y: -1500.-400., z: 0. },
-            end: Vec3::new(-520., -1500.-220., 0.),
-        },
-    );
-    commands.spawn((SpriteSheetBundle { // deck icon
+    Board{},
+    )).id();
+    move_entity(&mut commands, &settings, world_icon, EaseFunction::QuadraticInOut, Duration::from_secs(1), lens_bal, None);
+    let lens_bal = TransformPositionLens {
+        start: Vec3 { x: -520., y: -1500.-400., z: 0. },
+        end: Vec3::new(-520., -1500.-280., 0.),
+    };
+    let lens = TransformPositionLens {
+        start: Vec3 { x: -520., y: -1500.-400., z: 0. },
+        end: Vec3::new(-520., -1500.-220., 0.),
+    };
+    let deck_icon = commands.spawn((SpriteSheetBundle { // deck icon
         texture_atlas: texture_atlas_handle.clone(),
         sprite: TextureAtlasSprite{
             index : 10_usize,
@@ -559,10 +785,11 @@ fn distribute_starting_cards(mut commands: Commands, asset_server: Res<AssetServ
         },
         ..default()
     },
-    Animator::new(tween),
     Active{},
-    ));
-    commands.spawn((SpriteSheetBundle { // world icon
+    Board{},
+    )).id();
+    move_entity(&mut commands, &settings, deck_icon, EaseFunction::QuadraticInOut, Duration::from_secs(1), lens, None);
+    let world_icon = commands.spawn((SpriteSheetBundle { // world icon
         texture_atlas: texture_atlas_handle.clone(),
         sprite: TextureAtlasSprite{
             index : 8_usize,
@@ -577,8 +804,9 @@ fn distribute_starting_cards(mut commands: Commands, asset_server: Res<AssetServ
         ..default()
     },
     Active{},
-    Animator::new(tween_bal),
-    ));
+    Board{},
+    )).id();
+    move_entity(&mut commands, &settings, world_icon, EaseFunction::QuadraticInOut, Duration::from_secs(1), lens_bal, None);
     commands.spawn((SpriteSheetBundle {
         texture_atlas: texture_atlas_handle.clone(),
         sprite: TextureAtlasSprite{
@@ -593,6 +821,7 @@ fn distribute_starting_cards(mut commands: Commands, asset_server: Res<AssetServ
         ..default()
     },
     SwapSpace{},
+    Board{},
     ));
     commands.spawn((SpriteSheetBundle {
         texture_atlas: texture_atlas_handle.clone(),
@@ -609,6 +838,7 @@ fn distribute_starting_cards(mut commands: Commands, asset_server: Res<AssetServ
         ..default()
     },
     SwapSpace{},
+    Board{},
     ));
     commands.spawn(
         (
@@ -627,17 +857,35 @@ fn distribute_starting_cards(mut commands: Commands, asset_server: Res<AssetServ
             },
             FifthMarker{},
             Active{},
+            Board{},
         )
-    );
+    ).with_children(|parent| {
+        parent.spawn(key_hint(&hint_style, 4));
+    });
 
 }
 
+fn key_hint(style: &TextStyle, number: u8) -> (Text2dBundle, KeyHint){
+    (
+        Text2dBundle {
+            text: Text::from_section("", style.clone())
+            .with_alignment(TextAlignment::Center),
+            transform: Transform::from_xyz(0., -20., 0.),
+            ..default()
+        },
+        KeyHint{
+            number,
+        },
+    )
+}
+
 fn move_text_labels(
     query_world: Query<&WorldManager>,
     mut query: Query<(Entity, &mut TextLabel, &Transform)>,
     query_swap: Query<(Entity, &Transform), With<SwapSpace>>,
     query_swap_text: Query<(Entity, &Transform), With<FifthMarker>>,
     mut commands: Commands,
+    settings: Res<GameSettings>,
 ){
     if unsafe {
         WORLD_PHASE != 1 && WORLD_PHASE != 5
@@ -653,7 +901,10 @@ fn move_text_labels(
             let text_num = text.number;
             let bump_y = if pleroma { -1270.} else {220.};
             let bump_x = if pleroma { 160.} else {-40.};
-            let tween = Tween::new(
+            move_entity(
+                &mut commands,
+                &settings,
+                entity_id,
                 EaseFunction::QuadraticInOut,
                 Duration::from_secs(1),
                 TransformPositionLens {
@@ -661,32 +912,38 @@ fn move_text_labels(
                     end: Vec3::new(bump_x, bump_y-120.*text_num as f32, 0.),
 
                 },
-            ).with_completed(|_entity, _tween|{world_phase_update(3)});
-            commands.entity(entity_id).insert(Animator::new(tween));
+                Some(3),
+            );
             world_phase_update(2);
     
         }
         for (entity_id, transform) in query_swap.iter(){
-            let tween = Tween::new(
+            move_entity(
+                &mut commands,
+                &settings,
+                entity_id,
                 EaseFunction::QuadraticInOut,
                 Duration::from_secs(1),
                 TransformPositionLens {
                     start: transform.translation,
                     end: Vec3::new(transform.translation.x, transform.translation.y + 290., transform.translation.z),
                 },
+                None,
             );
-            commands.entity(entity_id).insert(Animator::new(tween));
         }
         for (entity_id, trans) in query_swap_text.iter(){
-            let tween = Tween::new(
+            move_entity(
+                &mut commands,
+                &settings,
+                entity_id,
                 EaseFunction::QuadraticInOut,
                 Duration::from_secs(1),
                 TransformPositionLens {
                     start: trans.translation,
                     end: Vec3::new(trans.translation.x, trans.translation.y+250., trans.translation.z),
                 },
+                None,
             );
-            commands.entity(entity_id).insert(Animator::new(tween));
         }
     }
     else if (unsafe { WORLD_PHASE } == 5){
@@ -698,38 +955,47 @@ fn move_text_labels(
             let text_num = text.number;
             let bump_y = if pleroma { -1500.} else {0.};
             let bump_x = if pleroma { 675.} else {0.};
-            let tween = Tween::new(
+            move_entity(
+                &mut commands,
+                &settings,
+                entity_id,
                 EaseFunction::QuadraticInOut,
                 Duration::from_secs(1),
                 TransformPositionLens {
                     end: Vec3::new(bump_x+-400.+80.*text_num as f32, bump_y+-300., 0.),
                     start: trans.translation,
                 },
-            ).with_completed(|_entity, _tween|{world_phase_update(0)});
-            commands.entity(entity_id).insert(Animator::new(tween));
+                Some(0),
+            );
             world_phase_update(6);
         }
         for (entity_id, transform) in query_swap.iter(){
-            let tween = Tween::new(
+            move_entity(
+                &mut commands,
+                &settings,
+                entity_id,
                 EaseFunction::QuadraticInOut,
                 Duration::from_secs(1),
                 TransformPositionLens {
                     start: transform.translation,
                     end: Vec3::new(transform.translation.x, transform.translation.y - 290., transform.translation.z),
                 },
+                None,
             );
-            commands.entity(entity_id).insert(Animator::new(tween));
         }
         for (entity_id, trans) in query_swap_text.iter(){
-            let tween = Tween::new(
+            move_entity(
+                &mut commands,
+                &settings,
+                entity_id,
                 EaseFunction::QuadraticInOut,
                 Duration::from_secs(1),
                 TransformPositionLens {
                     start: trans.translation,
                     end: Vec3::new(trans.translation.x, trans.translation.y-250., trans.translation.z),
                 },
+                None,
             );
-            commands.entity(entity_id).insert(Animator::new(tween));
         }
     }
 
@@ -742,10 +1008,52 @@ fn world_phase_update(new_phase: i8){
     }
 }
 
+fn move_entity(
+    commands: &mut Commands,
+    settings: &GameSettings,
+    entity: Entity,
+    ease: EaseFunction,
+    duration: Duration,
+    lens: TransformPositionLens,
+    next_phase: Option<i8>,
+){
+    if settings.instant_animations{ // skip the tween and land on its end position right away
+        let end = lens.end;
+        commands.entity(entity).remove::<Animator<Transform>>();
+        commands.add(move |world: &mut World|{
+            if let Some(mut transform) = world.get_mut::<Transform>(entity){
+                transform.translation = end;
+            }
+            if let Some(phase) = next_phase{ world_phase_update(phase) };
+        });
+        return;
+    }
+    let duration = duration.div_f32(settings.animation_speed);
+    if settings.reduced_motion() && lens.start.distance(lens.end) > REDUCED_MOTION_MAX_SLIDE{
+        let duration = duration.min(Duration::from_millis(300).div_f32(settings.animation_speed));
+        let tween = Tween::new(EaseFunction::QuadraticInOut, duration, JumpLens{ start: lens.start, end: lens.end });
+        let tween = match next_phase{
+            Some(phase) => tween.with_completed(move |_entity, _tween|{world_phase_update(phase)}),
+            None => tween,
+        };
+        let fade = Tween::new(EaseFunction::QuadraticInOut, duration, DissolveLens{});
+        commands.entity(entity).insert((Animator::new(tween), Animator::new(fade)));
+        return;
+    }
+    let ease = if settings.reduced_motion() && ease == EaseFunction::BackInOut { EaseFunction::QuadraticInOut } else { ease };
+    let tween = Tween::new(ease, duration, lens);
+    let tween = match next_phase{
+        Some(phase) => tween.with_completed(move |_entity, _tween|{world_phase_update(phase)}),
+        None => tween,
+    };
+    commands.entity(entity).insert(Animator::new(tween));
+}
+
 fn swap_pleroma_kenoma(
     mut query_world: Query<&mut WorldManager>,
     mut query_pleroma: Query<(Entity, &Transform, &Pleromic), With<Pleromic>>,
     mut commands: Commands,
+    settings: Res<GameSettings>,
 ){
     if unsafe {
         WORLD_PHASE != 8
@@ -762,15 +1070,18 @@ fn swap_pleroma_kenoma(
             let start_vec = trans.translation;
             let dist = item.dist;
             let end_vec = Vec3::new(start_vec.x+dist, start_vec.y, start_vec.z);
-            let tween = Tween::new(
+            move_entity(
+                &mut commands,
+                &settings,
+                entity_id,
                 EaseFunction::QuadraticInOut,
                 Duration::from_millis(300),
                 TransformPositionLens {
                     start: start_vec,
                     end: end_vec,
                 },
-            ).with_completed(|_entity, _tween|{world_phase_update(4)});
-            commands.entity(entity_id).insert(Animator::new(tween));
+                Some(4),
+            );
         }
     }
     else {
@@ -778,15 +1089,18 @@ fn swap_pleroma_kenoma(
             let start_vec = trans.translation;
             let dist = item.dist;
             let end_vec = Vec3::new(start_vec.x-dist, start_vec.y, start_vec.z);
-            let tween = Tween::new(
+            move_entity(
+                &mut commands,
+                &settings,
+                entity_id,
                 EaseFunction::QuadraticInOut,
                 Duration::from_millis(300),
                 TransformPositionLens {
                     start: start_vec,
                     end: end_vec,
                 },
-            ).with_completed(|_entity, _tween|{world_phase_update(4)});
-            commands.entity(entity_id).insert(Animator::new(tween));
+                Some(4),
+            );
         }
     }
     world_phase_update(-3);
@@ -795,11 +1109,11 @@ fn swap_pleroma_kenoma(
 fn claim_balanced_worlds(
     mut query: Query<(Entity, &mut PolarityMarker, &Transform)>,
     query_card: Query<&Card>,
-    query_world: Query<&WorldManager>,
+    mut session: ResMut<Session>,
     mut query_worlds: Query<(Entity, &mut Dimension)>,
-    mut query_w_deck: Query<&mut BalancedWorlds>,
-    mut query_text_deck: Query<&mut Text, With<BalancedWorlds>>,
+    mut query_w_deck: Query<(&mut BalancedWorlds, &mut Text)>,
     mut commands: Commands,
+    settings: Res<GameSettings>,
     asset_server: Res<AssetServer>, mut texture_atlases: ResMut<Assets<TextureAtlas>>
 )
 {
@@ -808,23 +1122,10 @@ fn claim_balanced_worlds(
     }{
         return;
     }
-    let mut card_offset = 0;
-    for card in query_card.iter() {
-        if !card.active{
-            continue;
-        }
-        card_offset = card.value;
-    }
-    for world in query_world.iter(){
-        if world.kenoma {card_offset = -card_offset};
-    }
-    let mut balanced_worlds: Vec<u8> = Vec::new();
-    for (_entity_id, pol , trans) in query.iter(){
-        if pol.polarity == 0 && trans.translation.y > -1000.{
-            balanced_worlds.push(pol.world);
-        }
-    }
-    assert!(balanced_worlds.len() < 5);
+    let Some(active) = query_card.iter().find(|card| card.active) else { return };
+    let outcome = session.play(Move::Claim{ slot: active.position });
+    debug_assert_eq!(outcome.card, active.value);
+    let balanced_worlds = outcome.balanced;
     for (entity_id, world) in query_worlds.iter_mut() {
         let world_num = world.world;
         if !balanced_worlds.contains(&world_num){
@@ -840,15 +1141,18 @@ fn claim_balanced_worlds(
         } else {
             Vec3{ x: 0.0, y: 760.0-(world_num as f32 * 120.0), z: 0.0}
         };
-        let tween = Tween::new(
+        move_entity(
+            &mut commands,
+            &settings,
+            entity_id,
             EaseFunction::QuadraticInOut,
             Duration::from_millis(300),
             TransformPositionLens {
                 start: start_vec,
                 end: end_vec,
             },
+            None,
         );
-        commands.entity(entity_id).insert(Animator::new(tween));
         commands.entity(entity_id).remove::<Dimension>();
         /*
         let possible_worlds = ["Goemorphos"];
@@ -880,15 +1184,7 @@ fn claim_balanced_worlds(
         } else {
             Color::rgb(1.0, 1.0, 1.0)
         };
-        let tween = Tween::new(
-            EaseFunction::QuadraticInOut,
-            Duration::from_millis(1300),
-            TransformPositionLens {
-                start: start_vec,
-                end: end_vec,
-            },
-        ).with_completed(|_entity, _tween|{world_phase_update(8)});
-        commands.spawn((SpriteSheetBundle {
+        let new_world = commands.spawn((SpriteSheetBundle {
             texture_atlas: texture_atlas_handle.clone(),
             sprite: TextureAtlasSprite{
                 index : 8, // replace this with the world texture
@@ -907,16 +1203,26 @@ fn claim_balanced_worlds(
             world: world_num,
             pleroma: world.pleroma,
         },
-        Animator::new(tween),
-        ));
+        Board{},
+        )).id();
+        move_entity(
+            &mut commands,
+            &settings,
+            new_world,
+            EaseFunction::QuadraticInOut,
+            Duration::from_millis(1300),
+            TransformPositionLens {
+                start: start_vec,
+                end: end_vec,
+            },
+            Some(8),
+        );
     }
     for (entity_id, mut pol, trans) in query.iter_mut(){
         if !balanced_worlds.contains(&pol.world){
             continue;
         }
-        assert!(pol.polarity == 0);
-        pol.polarity = card_offset;
-        assert!(pol.polarity != 0);
+        pol.polarity = session.game.polarity[pol.world as usize];
         let end_x = if pol.polarity > 0{
             190. + (pol.polarity-2) as f32*80.
         }
@@ -929,24 +1235,26 @@ fn claim_balanced_worlds(
         else {
             Vec3::new(end_x, 260.0-(pol.world as f32 * 120.0), 0.)
         };
-        let tween = Tween::new(
+        move_entity(
+            &mut commands,
+            &settings,
+            entity_id,
             EaseFunction::BackInOut,
             Duration::from_secs(1),
             TransformPositionLens {
                 start: trans.translation,
                 end: end_vector
             },
+            None,
         );
-        commands.entity(entity_id).insert(Animator::new(tween));
 
     }
-    let mut cap = 0;
-    for mut deck in query_w_deck.iter_mut(){
-        deck.capacity += balanced_worlds.len() as u16;
-        cap = deck.capacity;
-    }
-    for mut text in query_text_deck.iter_mut(){
-        text.sections[0].value = cap.to_string();
+    for (mut deck, mut text) in query_w_deck.iter_mut(){
+        deck.capacity = match deck.player{
+            Some(player) => session.game.scores[player as usize],
+            None => session.game.score,
+        };
+        text.sections[0].value = deck.capacity.to_string();
     }
     world_phase_update(-6);
     if balanced_worlds.is_empty(){ world_phase_update(8)};
@@ -958,6 +1266,8 @@ fn banish_and_replace(
     query_world: Query<&WorldManager>,
     mut query_deck: Query<&mut Deck>,
     mut commands: Commands,
+    session: Res<Session>,
+    settings: Res<GameSettings>,
     asset_server: Res<AssetServer>, mut texture_atlases: ResMut<Assets<TextureAtlas>>
 ){
     if unsafe {
@@ -970,21 +1280,24 @@ fn banish_and_replace(
     for world in query_world.iter(){
         if world.kenoma {pleroma = false};
     }
-    let card_value = rand::thread_rng().gen_range(1..7);
     for (entity_id, card, plero, trans) in query.iter_mut() {
         scan_count-=1;
         if !card.active{
             continue;
         }
-        let tween = Tween::new(
+        let card_value = session.game.hands[plero.pleroma as usize][card.position as usize];
+        move_entity(
+            &mut commands,
+            &settings,
+            entity_id,
             EaseFunction::QuadraticIn,
             Duration::from_millis(300),
             TransformPositionLens {
                 start: trans.translation,
                 end: Vec3::new(trans.translation.x, trans.translation.y-230., 0.),
             },
+            None,
         );
-        commands.entity(entity_id).insert(Animator::new(tween));
         commands.entity(entity_id).remove::<Card>();
         let img_path = "spritesheet.png".to_owned();
         let texture_handle = asset_server.load(&img_path);
@@ -1017,15 +1330,7 @@ fn banish_and_replace(
         } else {
             Color::rgb(1.0, 1.0, 1.0)
         };
-        let tween = Tween::new(
-            EaseFunction::QuadraticInOut,
-            Duration::from_secs(1),
-            TransformPositionLens {
-                start: start_vec,
-                end: end_vec,
-            },
-        );
-        commands.spawn((SpriteSheetBundle {
+        let new_card = commands.spawn((SpriteSheetBundle {
             texture_atlas: texture_atlas_handle.clone(),
             sprite: TextureAtlasSprite{
                 index : (card_value-1) as usize,
@@ -1045,18 +1350,29 @@ fn banish_and_replace(
             pleroma: plero.pleroma,
             dist: 675.
         },
-        Animator::new(tween),
-        )); 
+        Board{},
+        )).id();
+        move_entity(
+            &mut commands,
+            &settings,
+            new_card,
+            EaseFunction::QuadraticInOut,
+            Duration::from_secs(1),
+            TransformPositionLens {
+                start: start_vec,
+                end: end_vec,
+            },
+            None,
+        );
     }
-    let mut cap = 64;
+    let cap = session.game.deck.len() as u16;
     for mut deck in query_deck.iter_mut(){
-        deck.capacity -= 1;
-        cap = deck.capacity;
+        deck.capacity = cap;
     }
     for mut text in query_text_deck.iter_mut(){
         text.sections[0].value = cap.to_string();
     }
-    if cap == 0{
+    if session.game.is_over(){
         world_phase_update(-99);
         return;
     }
@@ -1067,127 +1383,131 @@ fn banish_and_replace(
 
 fn push_world_polarity(
     mut query: Query<(Entity, &mut PolarityMarker)>,
-    query_world: Query<&WorldManager>,
-    mut query_cards: Query<&mut Card>,
+    query_cards: Query<&Card>,
     mut commands: Commands,
-    input: Res<Input<KeyCode>>,
+    mut queue: ResMut<InputQueue>,
+    mut session: ResMut<Session>,
+    settings: Res<GameSettings>,
 ){
     if unsafe { WORLD_PHASE } == 3{
-        let mut pleroma = false;
-        for wor in query_world.iter(){
-            if !wor.kenoma{
-                pleroma = true;
-            }
-        }
-        let mut value_incoming = 0;
-        for card in query_cards.iter_mut() {
-            if card.active{ value_incoming = card.value}
-        }
-        if pleroma {value_incoming = -value_incoming};
-        if value_incoming == 0{ return;}
+        let Some(active) = query_cards.iter().find(|card| card.active) else { return };
+        let Some(action) = queue.next(|action| matches!(action, InputAction::TargetWorld(_) | InputAction::Claim)) else { return };
+        let InputAction::TargetWorld(key) = action else {
+            world_phase_update(7);
+            return;
+        };
+        let outcome = session.play(Move::Push{ slot: active.position, world: key });
+        debug_assert_eq!(outcome.card, active.value);
         for (entity_id, mut pol) in query.iter_mut() {
-            if input.just_released(KeyCode::Key5){
-                world_phase_update(7);
+            if pol.world != key{
+                continue;
             }
-            else if input.just_released(KeyCode::Key1)
-            || input.just_released(KeyCode::Key2)
-            || input.just_released(KeyCode::Key3)
-            || input.just_released(KeyCode::Key4) {
-                if input.just_released(KeyCode::Key1) && pol.world != 0
-                || input.just_released(KeyCode::Key2) && pol.world != 1
-                || input.just_released(KeyCode::Key3) && pol.world != 2
-                || input.just_released(KeyCode::Key4) && pol.world != 3
-                {
-                    continue;
-                }
-                let current_pol = pol.polarity;
-                pol.polarity -= value_incoming;
-                if pol.polarity > 6{
-                    pol.polarity = 6;
-                }
-                else if pol.polarity < -6{
-                    pol.polarity = -6;
-                }
-                let start_vector: Vec3;
-                let end_vector: Vec3;
-                let mut start_x = 0.;
-                let mut end_x = start_x;
-                if current_pol != 0{
-                    if current_pol > 0{
-                        start_x = 190.+ (current_pol-2) as f32*80.;
-                    }
-                    else {
-                        start_x = -110. + (current_pol+1) as f32*80.;
-                    }
+            let current_pol = pol.polarity;
+            pol.polarity = session.game.polarity[key as usize];
+            let start_vector: Vec3;
+            let end_vector: Vec3;
+            let mut start_x = 0.;
+            let mut end_x = start_x;
+            if current_pol != 0{
+                if current_pol > 0{
+                    start_x = 190.+ (current_pol-2) as f32*80.;
                 }
-                if pol.polarity != 0{
-                    if pol.polarity > 0{
-                        end_x = 190. + (pol.polarity-2) as f32*80.;
-                    }
-                    else {
-                        end_x = -110. + (pol.polarity+1) as f32*80.;
-                    }
+                else {
+                    start_x = -110. + (current_pol+1) as f32*80.;
                 }
-                if pol.dimension{
-                    start_vector = Vec3::new(start_x+120., -1500.+260.0-(pol.world as f32 * 120.0), 0.);
-                    end_vector = Vec3::new(end_x+120., -1500.+260.0-(pol.world as f32 * 120.0), 0.);
+            }
+            if pol.polarity != 0{
+                if pol.polarity > 0{
+                    end_x = 190. + (pol.polarity-2) as f32*80.;
                 }
                 else {
-                    start_vector = Vec3::new(start_x, 260.0-(pol.world as f32 * 120.0), 0.);
-                    end_vector = Vec3::new(end_x, 260.0-(pol.world as f32 * 120.0), 0.);
+                    end_x = -110. + (pol.polarity+1) as f32*80.;
                 }
-                let tween = Tween::new(
-                    EaseFunction::BackInOut,
-                    Duration::from_secs(1),
-                    TransformPositionLens {
-                        start: start_vector,
-                        end: end_vector
-                    },
-                );
-                commands.entity(entity_id).insert(Animator::new(tween));
-                unsafe{ WORLD_PHASE = 4};
             }
+            if pol.dimension{
+                start_vector = Vec3::new(start_x+120., -1500.+260.0-(pol.world as f32 * 120.0), 0.);
+                end_vector = Vec3::new(end_x+120., -1500.+260.0-(pol.world as f32 * 120.0), 0.);
+            }
+            else {
+                start_vector = Vec3::new(start_x, 260.0-(pol.world as f32 * 120.0), 0.);
+                end_vector = Vec3::new(end_x, 260.0-(pol.world as f32 * 120.0), 0.);
+            }
+            move_entity(
+                &mut commands,
+                &settings,
+                entity_id,
+                EaseFunction::BackInOut,
+                Duration::from_secs(1),
+                TransformPositionLens {
+                    start: start_vector,
+                    end: end_vector
+                },
+                None,
+            );
+            unsafe{ WORLD_PHASE = 4};
         }
     }
 }
 
 fn select_card(
-    mut query: Query<(Entity, &mut Card, &Transform)>,
+    mut query: Query<(Entity, &mut Card, &Pleromic, &Transform)>,
     mut commands: Commands,
-    input: Res<Input<KeyCode>>,
+    mut queue: ResMut<InputQueue>,
+    session: Res<Session>,
+    settings: Res<GameSettings>,
 ) {
     if unsafe { WORLD_PHASE } == 0{
-        for (entity_id, mut card, trans) in query.iter_mut() {
+        let Some(InputAction::SelectSlot(key)) = queue.next(|action| matches!(action, InputAction::SelectSlot(_))) else { return };
+        // Both hands hold the same cards and are replaced together, unless each dimension has its own.
+        let own_hands = session.game.mode == Mode::Dimensions;
+        for (entity_id, mut card, plero, trans) in query.iter_mut() {
             let card_num = card.position;
-            let tween = Tween::new(
+            if card_num != key || (own_hands && plero.pleroma != session.game.pleroma){
+                continue;
+            }
+            move_entity(
+                &mut commands,
+                &settings,
+                entity_id,
                 EaseFunction::QuadraticInOut,
                 Duration::from_millis(300),
                 TransformPositionLens {
                     start: trans.translation,
                     end: Vec3::new(trans.translation.x, trans.translation.y+30., 0.),
                 },
-            ).with_completed(|_entity, _tween|{world_phase_update(1)});
-            if input.just_released(KeyCode::Key1)
-            || input.just_released(KeyCode::Key2)
-            || input.just_released(KeyCode::Key3)
-            || input.just_released(KeyCode::Key4) {
-                if input.just_released(KeyCode::Key1) && card_num != 0
-                || input.just_released(KeyCode::Key2) && card_num != 1
-                || input.just_released(KeyCode::Key3) && card_num != 2
-                || input.just_released(KeyCode::Key4) && card_num != 3
-                {
-                    continue;
-                }
-                commands.entity(entity_id).insert(Animator::new(tween));
-                card.active = true;
-            }
+                Some(1),
+            );
+            card.active = true;
         }
-        if input.just_released(KeyCode::Key1)
-        || input.just_released(KeyCode::Key2)
-        || input.just_released(KeyCode::Key3)
-        || input.just_released(KeyCode::Key4) {
         unsafe { WORLD_PHASE = -4 };
-        }
     }
 }
 
+fn adjust_motion_settings(
+    input: Res<Input<KeyCode>>,
+    bindings: Res<Bindings>,
+    mut settings: ResMut<GameSettings>,
+){
+    let mut changed = false;
+    if bindings.just_released(&input, InputAction::SlowerAnimations){
+        let speed = settings.animation_speed / 2.;
+        settings.set_animation_speed(speed);
+        changed = true;
+    }
+    if bindings.just_released(&input, InputAction::FasterAnimations){
+        let speed = settings.animation_speed * 2.;
+        settings.set_animation_speed(speed);
+        changed = true;
+    }
+    if bindings.just_released(&input, InputAction::ToggleInstantAnimations){
+        settings.instant_animations = !settings.instant_animations;
+        changed = true;
+    }
+    if bindings.just_released(&input, InputAction::ToggleReducedMotion){
+        settings.reduced_motion_choice = Some(!settings.reduced_motion());
+        changed = true;
+    }
+    if changed{
+        settings.save();
+    }
+}
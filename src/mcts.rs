@@ -0,0 +1,183 @@
+use std::time::{Duration, Instant};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use crate::{bot::{greedy_move, Bot}, rules::{Game, Move}};
+
+// Monte Carlo tree search for a player who cannot see the deck. Each iteration deals the
+// unseen cards afresh, so a move leads to one child per card it might draw, visited about as
+// often as that card turns up. The tree is thrown away after every move.
+
+const ROLLOUT_RANDOMNESS: f64 = 0.25; // otherwise rollouts follow greedy_move
+const EXPLORATION: f64 = 0.7;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Budget{
+    pub iterations: u32,
+    pub time: Option<Duration>, // stops early once spent; not available on the web
+}
+
+impl Budget{
+    pub fn iterations(iterations: u32) -> Self{
+        Budget{ iterations, time: None }
+    }
+}
+
+struct Edge{
+    mv: Move,
+    visits: u32,
+    total: f64, // worlds balanced from this move to the end of the game, summed over visits
+    draws: Vec<(Option<i8>, usize)>, // the node reached for each card drawn after the move
+}
+
+struct Node{
+    visits: u32,
+    edges: Vec<Edge>,
+}
+
+impl Node{
+    fn new(game: &Game) -> Self{
+        let edges = game.distinct_moves().into_iter()
+            .map(|mv| Edge{ mv, visits: 0, total: 0., draws: Vec::new() })
+            .collect();
+        Node{ visits: 0, edges }
+    }
+
+    // UCB1 on the worlds balanced per remaining turn, which keeps rewards on the same scale
+    // early and late in the game.
+    fn select(&self, turns_left: f64, rng: &mut ChaCha8Rng) -> usize{
+        if let Some(untried) = self.edges.iter().position(|edge| edge.visits == 0){
+            return untried;
+        }
+        let log_visits = (self.visits as f64).ln();
+        let mut best = (0, f64::MIN);
+        for (index, edge) in self.edges.iter().enumerate(){
+            let mean = edge.total / edge.visits as f64 / turns_left;
+            let score = mean + EXPLORATION * (log_visits / edge.visits as f64).sqrt() + rng.gen::<f64>() * 1e-9;
+            if score > best.1{
+                best = (index, score);
+            }
+        }
+        best.0
+    }
+}
+
+pub struct Mcts{
+    pub budget: Budget,
+    rng: ChaCha8Rng,
+}
+
+impl Mcts{
+    pub fn new(budget: Budget, seed: u64) -> Self{
+        Mcts{ budget, rng: ChaCha8Rng::seed_from_u64(seed) }
+    }
+
+    // The same game with the unseen cards dealt at random.
+    fn determinize(&mut self, game: &Game) -> Game{
+        let mut guess = game.clone();
+        for card in guess.deck.iter_mut(){
+            *card = self.rng.gen_range(1..=game.rules.max_card);
+        }
+        guess
+    }
+
+    fn rollout(&mut self, mut game: Game) -> f64{
+        let start = game.score;
+        while !game.is_over(){
+            let mv = if self.rng.gen_bool(ROLLOUT_RANDOMNESS){
+                let moves = game.legal_moves();
+                moves[self.rng.gen_range(0..moves.len())]
+            } else {
+                greedy_move(&game, &mut self.rng)
+            };
+            game.apply(mv).expect("rollouts only play legal moves");
+        }
+        (game.score - start) as f64
+    }
+
+    // Every distinct move from game with its expected worlds to come and its visit count,
+    // most visited first.
+    pub fn evaluate(&mut self, game: &Game) -> Vec<(Move, f64, u32)>{
+        if game.is_over(){
+            return Vec::new();
+        }
+        let started = self.budget.time.map(|_| Instant::now());
+        let mut nodes = vec![Node::new(game)];
+        for iteration in 0..self.budget.iterations.max(1){
+            if let (Some(started), Some(time)) = (started, self.budget.time){
+                if iteration % 64 == 0 && started.elapsed() >= time{
+                    break;
+                }
+            }
+            let mut sim = self.determinize(game);
+            let mut path: Vec<(usize, usize, u16)> = Vec::new(); // node, edge, score before the move
+            let mut node = 0;
+            loop{
+                if sim.is_over(){
+                    break;
+                }
+                let turns_left = sim.deck.len() as f64;
+                let edge = nodes[node].select(turns_left, &mut self.rng);
+                path.push((node, edge, sim.score));
+                let outcome = sim.apply(nodes[node].edges[edge].mv).expect("the tree only holds legal moves");
+                let known = nodes[node].edges[edge].draws.iter().find(|(drawn, _)| *drawn == outcome.drawn).map(|(_, child)| *child);
+                match known{
+                    Some(child) => node = child,
+                    None => {
+                        let child = nodes.len();
+                        nodes.push(Node::new(&sim));
+                        nodes[node].edges[edge].draws.push((outcome.drawn, child));
+                        break; // one new node per iteration, the rollout estimates the rest
+                    }
+                }
+            }
+            let rollout = self.rollout(sim.clone());
+            let end = sim.score as f64 + rollout;
+            for (node, edge, before) in path{
+                nodes[node].visits += 1;
+                let edge = &mut nodes[node].edges[edge];
+                edge.visits += 1;
+                edge.total += end - before as f64;
+            }
+        }
+        let mut moves: Vec<(Move, f64, u32)> = nodes[0].edges.iter()
+            .map(|edge| (edge.mv, edge.total / edge.visits.max(1) as f64, edge.visits))
+            .collect();
+        moves.sort_by(|a, b| b.2.cmp(&a.2).then(b.1.total_cmp(&a.1)));
+        moves
+    }
+}
+
+impl Bot for Mcts{
+    fn name(&self) -> String{
+        format!("mcts-{}", self.budget.iterations)
+    }
+
+    fn choose(&mut self, game: &Game) -> Move{
+        self.evaluate(game).first().map(|(mv, _, _)| *mv).expect("the game is not over")
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use crate::{bot::{play_out, RandomBot}, rules::RulePreset};
+    use super::*;
+
+    #[test]
+    fn a_real_budget_beats_random_play(){
+        let seeds = 0..4;
+        let mean = |bot: &mut dyn Bot| seeds.clone().map(|seed| play_out(Game::new(RulePreset::Quick.rules(), seed), bot).score as f64).sum::<f64>() / seeds.len() as f64;
+        let (searched, random) = (mean(&mut Mcts::new(Budget::iterations(300), 1)), mean(&mut RandomBot::new(1)));
+        assert!(searched > random, "search {searched} against random {random}");
+    }
+
+    #[test]
+    fn evaluate_covers_the_distinct_moves(){
+        let game = Game::new(RulePreset::Classic.rules(), 2);
+        let evaluated = Mcts::new(Budget::iterations(200), 3).evaluate(&game);
+        let mut moves: Vec<Move> = evaluated.iter().map(|(mv, _, _)| *mv).collect();
+        assert!(evaluated.windows(2).all(|pair| pair[0].2 >= pair[1].2)); // most visited first
+        moves.sort_by_key(|mv| mv.index());
+        assert_eq!(moves, game.distinct_moves());
+        assert_eq!(evaluated, Mcts::new(Budget::iterations(200), 3).evaluate(&game));
+    }
+}
@@ -0,0 +1,721 @@
+use bevy::{prelude::*, app::AppExit};
+use bevy_tweening::{Animator, AnimatorState};
+use pleromakenoma::{code::GameCode, generator::Difficulty, puzzle::Verdict, rules::{Mode, RulePreset}};
+use crate::{analysis::{self, Analysis}, hint::{hint_strength_name, HINT_STRENGTHS}, achievements::{Achievement, Achievements, Toast}, daily::{self, DailyRecord}, date, input::{Bindings, InputAction}, network, puzzles::{self, Puzzles}, scores::Leaderboard, session::{NextGame, Session}, stats::LifetimeStats, storage, versus, Board, GameSettings, WORLD_PHASE};
+
+const SPEEDS: [f32; 5] = [0.25, 0.5, 1., 2., 4.];
+const SCORES_SHOWN: usize = 8;
+
+#[derive(States, Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub enum AppState{
+    #[default]
+    MainMenu,
+    Settings,
+    Dealing, // the board is being (re)spawned, Playing follows on the next frame
+    Playing,
+    Paused,
+    Modes,
+    GameOver,
+    Stats,
+    Import,
+    Analysis,
+    Network,
+    Puzzles,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MenuButton{
+    NewGame,
+    Continue,
+    Daily,
+    Modes,
+    Settings,
+    Stats,
+    Quit,
+    Resume,
+    MainMenu,
+    Back,
+    Preset(RulePreset),
+    Versus,
+    ScoreFilter,
+    ExportStats,
+    ImportCode,
+    LoadCode,
+    Analyse,
+    PreviousTurn,
+    NextTurn,
+    WorstMistake,
+    Network,
+    Host,
+    Join,
+    Watch,
+    Puzzles,
+    Puzzle(usize), // an index into the pack
+    Retry,
+    NextPuzzle,
+    FreshPuzzle,
+    Setting(Setting), // a line of the settings screen, which changes as it is activated
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Setting{
+    InputBuffering,
+    AnimationSpeed,
+    InstantAnimations,
+    ReducedMotion,
+    MoveLog,
+    HintStrength,
+    PuzzleDifficulty,
+    KeyPreset,
+    SpectatorStream,
+    Profile,
+}
+
+#[derive(Event)]
+pub struct MenuActivated(pub MenuButton);
+
+#[derive(Component)]
+pub struct MenuScreen{} // root node of whichever menu is open
+
+#[derive(Component)]
+struct MenuItem{
+    index: usize,
+    enabled: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct MenuCursor{
+    pub index: usize,
+}
+
+#[derive(Resource)]
+struct SettingsReturn(AppState); // the settings screen opens from both the main menu and the pause overlay
+
+#[derive(Resource, Default)]
+struct ScoreFilter(Option<RulePreset>); // None shows every preset
+
+#[derive(Resource, Default)]
+struct ImportText{
+    text: String,
+    error: Option<String>,
+}
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin{
+    fn build(&self, app: &mut App){
+        app.add_state::<AppState>()
+            .add_event::<MenuActivated>()
+            .init_resource::<MenuCursor>()
+            .insert_resource(SettingsReturn(AppState::MainMenu))
+            .init_resource::<ScoreFilter>()
+            .init_resource::<ImportText>()
+            .init_resource::<Analysis>()
+            .add_systems(OnEnter(AppState::MainMenu), (spawn_main_menu, network::disconnect))
+            .add_systems(OnExit(AppState::MainMenu), despawn_menu)
+            .add_systems(OnEnter(AppState::Settings), spawn_settings)
+            .add_systems(OnExit(AppState::Settings), despawn_menu)
+            .add_systems(OnEnter(AppState::Paused), spawn_pause_menu)
+            .add_systems(OnExit(AppState::Paused), despawn_menu)
+            .add_systems(OnEnter(AppState::Modes), spawn_modes)
+            .add_systems(OnExit(AppState::Modes), despawn_menu)
+            .add_systems(OnEnter(AppState::GameOver), (puzzles::record_solved, spawn_game_over).chain())
+            .add_systems(OnExit(AppState::GameOver), despawn_menu)
+            .add_systems(OnEnter(AppState::Stats), spawn_stats)
+            .add_systems(OnExit(AppState::Stats), despawn_menu)
+            .add_systems(OnEnter(AppState::Import), spawn_import)
+            .add_systems(OnExit(AppState::Import), despawn_menu)
+            .add_systems(OnEnter(AppState::Analysis), analysis::spawn_analysis)
+            .add_systems(OnExit(AppState::Analysis), (despawn_menu, analysis::cancel_analysis))
+            .add_systems(OnEnter(AppState::Network), network::spawn_network)
+            .add_systems(OnExit(AppState::Network), despawn_menu)
+            .add_systems(OnEnter(AppState::Puzzles), puzzles::spawn_puzzles)
+            .add_systems(OnExit(AppState::Puzzles), despawn_menu)
+            .add_systems(OnEnter(AppState::Playing), resume_tweens)
+            .add_systems(OnExit(AppState::Playing), freeze_tweens)
+            .add_systems(Update, pause_game.run_if(in_state(AppState::Playing)))
+            .add_systems(Update, edit_import_code.before(navigate_menu).run_if(in_state(AppState::Import)))
+            .add_systems(Update, network::step_network.before(navigate_menu).run_if(in_state(AppState::Network)))
+            .add_systems(Update, puzzles::receive_pack)
+            .add_systems(Update, puzzles::step_puzzles.before(navigate_menu).run_if(in_state(AppState::Puzzles)))
+            .add_systems(Update, puzzles::continue_puzzles.run_if(in_state(AppState::GameOver)))
+            .add_systems(Update, analysis::step_analysis.before(highlight_menu_item).run_if(in_state(AppState::Analysis)))
+            .add_systems(Update, (navigate_menu, activate_menu_button, filter_scores, export_stats, highlight_menu_item).chain()
+                .run_if(in_menu));
+    }
+}
+
+fn in_menu(state: Res<State<AppState>>) -> bool{
+    !matches!(state.get(), AppState::Dealing | AppState::Playing)
+}
+
+pub fn spawn_menu(commands: &mut Commands, asset_server: &AssetServer, title: &str, lines: Vec<String>, items: Vec<(MenuButton, String, bool)>, backdrop: Color){
+    let font = asset_server.load("Play-Regular.ttf");
+    let item_height = if items.len() > 10 { 40. } else { 44. }; // a long list still fits the window
+    commands.spawn((NodeBundle {
+        style: Style {
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        background_color: backdrop.into(),
+        ..default()
+    },
+    MenuScreen{},
+    )).with_children(|parent| {
+        parent.spawn(TextBundle::from_section(title, TextStyle {
+            font: font.clone(),
+            font_size: 48.0,
+            color: Color::WHITE,
+        }).with_style(Style {
+            margin: UiRect::bottom(Val::Px(30.)),
+            ..default()
+        }));
+        for line in lines{ // plain text between the title and the buttons
+            parent.spawn(TextBundle::from_section(line, TextStyle {
+                font: font.clone(),
+                font_size: 20.0,
+                color: Color::rgb(0.8, 0.8, 0.8),
+            }));
+        }
+        for (index, (button, label, enabled)) in items.into_iter().enumerate(){
+            parent.spawn((ButtonBundle {
+                style: Style {
+                    width: Val::Px(320.),
+                    height: Val::Px(item_height),
+                    margin: UiRect::all(Val::Px(4.)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::NONE.into(),
+                ..default()
+            },
+            button,
+            MenuItem{ index, enabled },
+            )).with_children(|parent| {
+                parent.spawn(TextBundle::from_section(label, TextStyle {
+                    font: font.clone(),
+                    font_size: 26.0,
+                    color: if enabled { Color::WHITE } else { Color::rgb(0.35, 0.35, 0.35) },
+                }));
+            });
+        }
+    });
+}
+
+fn despawn_menu(mut commands: Commands, query: Query<Entity, With<MenuScreen>>){
+    for entity in query.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn spawn_main_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut cursor: ResMut<MenuCursor>,
+    daily_record: Res<DailyRecord>,
+    query_board: Query<(), With<Board>>,
+){
+    let can_continue = !query_board.is_empty() && unsafe { WORLD_PHASE } != -99;
+    let today = daily::today();
+    let daily_label = match (daily_record.played(today), daily_record.last_score){
+        (true, Some(score)) => format!("Daily: {score} today"),
+        (true, None) => "Daily: played today".to_owned(),
+        (false, _) => format!("Daily (streak {})", daily_record.current_streak(today)),
+    };
+    let items = vec![
+        (MenuButton::NewGame, "New Game".to_owned(), true),
+        (MenuButton::Continue, "Continue".to_owned(), can_continue),
+        (MenuButton::Daily, daily_label, !daily_record.played(today)),
+        (MenuButton::Puzzles, "Puzzles".to_owned(), true),
+        (MenuButton::Modes, "Modes".to_owned(), true),
+        (MenuButton::Network, "Network".to_owned(), true),
+        (MenuButton::Settings, "Settings".to_owned(), true),
+        (MenuButton::Stats, "Stats".to_owned(), true),
+        (MenuButton::ImportCode, "Import Code".to_owned(), true),
+        (MenuButton::Quit, "Quit".to_owned(), true),
+    ];
+    cursor.index = if can_continue { 1 } else { 0 };
+    spawn_menu(&mut commands, &asset_server, "Pleroma & Kenoma", Vec::new(), items, Color::BLACK);
+}
+
+fn spawn_pause_menu(mut commands: Commands, asset_server: Res<AssetServer>, session: Res<Session>, mut cursor: ResMut<MenuCursor>){
+    let items = vec![
+        (MenuButton::Resume, "Resume".to_owned(), true),
+        (MenuButton::Settings, "Settings".to_owned(), true),
+        (MenuButton::MainMenu, "Main Menu".to_owned(), true),
+        (MenuButton::Quit, "Quit".to_owned(), true),
+    ];
+    cursor.index = 0;
+    let lines = match &session.puzzle{
+        Some(puzzle) => vec![format!("{}: {}", puzzle.name, puzzle.goal.describe()), String::new()],
+        None => vec![session.code().encode(), String::new()],
+    };
+    spawn_menu(&mut commands, &asset_server, "Paused", lines, items, Color::rgba(0., 0., 0., 0.8));
+}
+
+fn settings_items(settings: &GameSettings, bindings: &Bindings) -> Vec<(MenuButton, String, bool)>{
+    let on_off = |flag: bool| if flag { "On" } else { "Off" };
+    vec![
+        (MenuButton::Setting(Setting::InputBuffering), format!("Input buffering: {}", on_off(settings.input_buffering)), true),
+        (MenuButton::Setting(Setting::AnimationSpeed), format!("Animation speed: {}x", settings.animation_speed), !settings.instant_animations),
+        (MenuButton::Setting(Setting::InstantAnimations), format!("Instant animations: {}", on_off(settings.instant_animations)), true),
+        (MenuButton::Setting(Setting::ReducedMotion), format!("Reduced motion: {}{}", on_off(settings.reduced_motion()), if settings.reduced_motion_choice.is_none() { " (system)" } else { "" }), true),
+        (MenuButton::Setting(Setting::MoveLog), format!("Move log: {}", on_off(settings.show_move_log)), true),
+        (MenuButton::Setting(Setting::HintStrength), format!("Hints: {}", hint_strength_name(settings.hint_strength)), true),
+        (MenuButton::Setting(Setting::PuzzleDifficulty), format!("Fresh puzzles: {}", settings.puzzle_difficulty.name()), true),
+        (MenuButton::Setting(Setting::KeyPreset), format!("Keys: {:?}", bindings.preset), true),
+        (MenuButton::Setting(Setting::SpectatorStream), format!("Spectator stream: {}", on_off(settings.spectator_stream)), cfg!(not(target_arch = "wasm32"))),
+        (MenuButton::Setting(Setting::Profile), format!("Profile: {}", settings.profile), cfg!(not(target_arch = "wasm32"))),
+        (MenuButton::Back, "Back".to_owned(), true),
+    ]
+}
+
+fn spawn_settings(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<GameSettings>,
+    bindings: Res<Bindings>,
+    mut cursor: ResMut<MenuCursor>,
+){
+    cursor.index = 0;
+    spawn_menu(&mut commands, &asset_server, "Settings", Vec::new(), settings_items(&settings, &bindings), Color::BLACK);
+}
+
+fn modes_items(next_game: &NextGame) -> Vec<(MenuButton, String, bool)>{
+    let mut items: Vec<_> = RulePreset::ALL.iter().map(|preset| {
+        let rules = preset.rules();
+        let label = format!("{}: {} cards, clamp {}", preset.name(), rules.deck_size, rules.clamp);
+        (MenuButton::Preset(*preset), label, true)
+    }).collect();
+    items.push((MenuButton::Versus, format!("Players: {}", next_game.mode.name()), true));
+    items.push((MenuButton::Back, "Back".to_owned(), true));
+    items
+}
+
+fn spawn_modes(mut commands: Commands, asset_server: Res<AssetServer>, next_game: Res<NextGame>, mut cursor: ResMut<MenuCursor>){
+    cursor.index = RulePreset::ALL.iter().position(|preset| *preset == next_game.preset).unwrap_or(0);
+    spawn_menu(&mut commands, &asset_server, "Modes", Vec::new(), modes_items(&next_game), Color::BLACK);
+}
+
+fn format_duration(secs: f32) -> String{
+    let secs = secs as u32;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+fn game_over_lines(session: &Session, daily_record: &DailyRecord, leaderboard: &Leaderboard, filter: Option<RulePreset>) -> Vec<String>{
+    if session.puzzle.is_some(){ // no seed to share and no scores to compare
+        return puzzles::game_over_lines(session);
+    }
+    let game = &session.game;
+    let mut lines = vec![
+        format!("{} balanced worlds in {} ({}, seed {:08x})", game.score, format_duration(session.elapsed), session.preset.name(), session.seed),
+    ];
+    if game.mode.is_versus(){
+        let [first, second] = game.scores;
+        lines.push(match first.cmp(&second){
+            std::cmp::Ordering::Equal => format!("A draw, {first} each"),
+            _ => format!("{} wins, {} to {}", versus::player_name(game.mode, (second > first) as u8), first.max(second), first.min(second)),
+        });
+    }
+    lines.push(session.code().encode());
+    if let Some(day) = session.daily{
+        lines.push(format!("Daily {}, streak {} (best {})", date::format_date(day * 86_400), daily_record.streak, daily_record.best_streak));
+    }
+    lines.push(String::new());
+    for (rank, record) in leaderboard.filtered(filter).take(SCORES_SHOWN).enumerate(){
+        lines.push(format!("{}.  {}  {}  {}  {}  {:08x}", rank + 1, record.score, record.preset.name(),
+            date::format_date(record.date), format_duration(record.duration), record.seed));
+    }
+    if leaderboard.filtered(filter).next().is_none(){
+        lines.push("No scores yet".to_owned());
+    }
+    lines.push(String::new());
+    lines
+}
+
+fn game_over_items(filter: Option<RulePreset>, session: &Session, puzzles: &Puzzles) -> Vec<(MenuButton, String, bool)>{
+    if session.puzzle.is_some(){
+        return puzzles::game_over_items(puzzles);
+    }
+    vec![
+        (MenuButton::ScoreFilter, format!("Show: {}", filter.map_or("All", |preset| preset.name())), true),
+        (MenuButton::NewGame, "New Game".to_owned(), true),
+        (MenuButton::Analyse, "Analyse".to_owned(), !session.game.mode.is_versus()), // the solver plays for the total
+        (MenuButton::MainMenu, "Main Menu".to_owned(), true),
+    ]
+}
+
+fn spawn_game_over(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    session: Res<Session>,
+    daily_record: Res<DailyRecord>,
+    leaderboard: Res<Leaderboard>,
+    puzzles: Res<Puzzles>,
+    mut filter: ResMut<ScoreFilter>,
+    mut cursor: ResMut<MenuCursor>,
+){
+    filter.0 = None;
+    cursor.index = match session.puzzle{ // New Game, or Retry until a puzzle is solved
+        Some(_) if session.verdict != Verdict::Solved || puzzles.next().is_none() => 0,
+        _ => 1,
+    };
+    let lines = game_over_lines(&session, &daily_record, &leaderboard, filter.0);
+    spawn_menu(&mut commands, &asset_server, "Game Over", lines, game_over_items(filter.0, &session, &puzzles), Color::rgba(0., 0., 0., 0.8));
+}
+
+fn stats_lines(stats: &LifetimeStats, achievements: &Achievements, status: Option<String>) -> Vec<String>{
+    let mut lines: Vec<String> = stats.rows().into_iter()
+        .map(|(name, value)| format!("{}: {}", name.replace('_', " "), if value.is_empty() { "-".to_owned() } else { value }))
+        .collect();
+    lines.push(String::new());
+    for achievement in Achievement::ALL{
+        let mark = if achievements.is_unlocked(achievement) { "[x]" } else { "[ ]" };
+        lines.push(format!("{mark} {}: {}", achievement.name(), achievement.description()));
+    }
+    lines.push(String::new());
+    lines.extend(status);
+    lines
+}
+
+fn stats_items() -> Vec<(MenuButton, String, bool)>{
+    vec![
+        (MenuButton::ExportStats, "Export CSV & JSON".to_owned(), cfg!(not(target_arch = "wasm32"))),
+        (MenuButton::Back, "Back".to_owned(), true),
+    ]
+}
+
+fn spawn_stats(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    stats: Res<LifetimeStats>,
+    achievements: Res<Achievements>,
+    mut cursor: ResMut<MenuCursor>,
+){
+    cursor.index = 1;
+    spawn_menu(&mut commands, &asset_server, &format!("Stats: {}", stats.profile), stats_lines(&stats, &achievements, None), stats_items(), Color::BLACK);
+}
+
+fn export_stats(
+    mut commands: Commands,
+    mut events: EventReader<MenuActivated>,
+    asset_server: Res<AssetServer>,
+    stats: Res<LifetimeStats>,
+    achievements: Res<Achievements>,
+    query_screen: Query<Entity, With<MenuScreen>>,
+){
+    for MenuActivated(button) in events.iter(){
+        if *button != MenuButton::ExportStats{
+            continue;
+        }
+        let status = match stats.export(){
+            Some(dir) => format!("Exported to {dir}"),
+            None => "Nowhere to export to".to_owned(),
+        };
+        for entity in query_screen.iter(){
+            commands.entity(entity).despawn_recursive();
+        }
+        spawn_menu(&mut commands, &asset_server, &format!("Stats: {}", stats.profile), stats_lines(&stats, &achievements, Some(status)), stats_items(), Color::BLACK);
+    }
+}
+
+fn import_lines(import: &ImportText) -> Vec<String>{
+    let mut lines = vec![
+        "Type a game code, then press Enter".to_owned(),
+        format!("> {}_", import.text),
+    ];
+    lines.extend(import.error.clone());
+    lines.push(String::new());
+    lines
+}
+
+fn import_items(import: &ImportText) -> Vec<(MenuButton, String, bool)>{
+    vec![
+        (MenuButton::LoadCode, "Load".to_owned(), !import.text.is_empty()),
+        (MenuButton::Back, "Back".to_owned(), true),
+    ]
+}
+
+fn spawn_import(mut commands: Commands, asset_server: Res<AssetServer>, mut import: ResMut<ImportText>, mut cursor: ResMut<MenuCursor>){
+    import.error = None;
+    cursor.index = 0;
+    spawn_menu(&mut commands, &asset_server, "Import Code", import_lines(&import), import_items(&import), Color::BLACK);
+}
+
+fn edit_import_code(
+    mut commands: Commands,
+    mut characters: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut events: EventReader<MenuActivated>,
+    asset_server: Res<AssetServer>,
+    mut import: ResMut<ImportText>,
+    mut next_game: ResMut<NextGame>,
+    mut next_state: ResMut<NextState<AppState>>,
+    query_screen: Query<Entity, With<MenuScreen>>,
+){
+    let mut changed = false;
+    for typed in characters.iter(){
+        if typed.char.is_ascii_alphanumeric() || typed.char == '-'{
+            import.text.push(typed.char.to_ascii_uppercase());
+            changed = true;
+        }
+    }
+    if keys.just_pressed(KeyCode::Back){
+        changed |= import.text.pop().is_some();
+    }
+    if changed{
+        import.error = None;
+    }
+    for MenuActivated(button) in events.iter(){
+        if *button != MenuButton::LoadCode{
+            continue;
+        }
+        match GameCode::decode(&import.text).and_then(|code| code.replay().map(|_| code)){
+            Ok(code) => {
+                next_game.preset = code.preset;
+                next_game.mode = code.mode;
+                next_game.seed = Some(code.seed);
+                next_game.moves = code.moves;
+                next_state.set(AppState::Dealing);
+                import.text.clear();
+                return;
+            }
+            Err(err) => {
+                import.error = Some(format!("Cannot load this code: {err}"));
+                changed = true;
+            }
+        }
+    }
+    if !changed{
+        return;
+    }
+    for entity in query_screen.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_menu(&mut commands, &asset_server, "Import Code", import_lines(&import), import_items(&import), Color::BLACK);
+}
+
+fn filter_scores(
+    mut commands: Commands,
+    mut events: EventReader<MenuActivated>,
+    asset_server: Res<AssetServer>,
+    session: Res<Session>,
+    daily_record: Res<DailyRecord>,
+    leaderboard: Res<Leaderboard>,
+    puzzles: Res<Puzzles>,
+    mut filter: ResMut<ScoreFilter>,
+    query_screen: Query<Entity, With<MenuScreen>>,
+){
+    for MenuActivated(button) in events.iter(){
+        if *button != MenuButton::ScoreFilter{
+            continue;
+        }
+        filter.0 = match filter.0{ // All, then each preset in turn
+            None => Some(RulePreset::ALL[0]),
+            Some(preset) => RulePreset::ALL.iter().position(|other| *other == preset)
+                .and_then(|index| RulePreset::ALL.get(index + 1)).copied(),
+        };
+        for entity in query_screen.iter(){
+            commands.entity(entity).despawn_recursive();
+        }
+        let lines = game_over_lines(&session, &daily_record, &leaderboard, filter.0);
+        spawn_menu(&mut commands, &asset_server, "Game Over", lines, game_over_items(filter.0, &session, &puzzles), Color::rgba(0., 0., 0., 0.8));
+    }
+}
+
+fn navigate_menu(
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    state: Res<State<AppState>>,
+    mut cursor: ResMut<MenuCursor>,
+    query_mouse: Query<(&Interaction, &MenuItem), Changed<Interaction>>,
+    query_items: Query<(&MenuItem, &MenuButton)>,
+    mut events: EventWriter<MenuActivated>,
+){
+    let pad = |button_type| gamepads.iter().any(|gamepad| buttons.just_pressed(GamepadButton::new(gamepad, button_type)));
+    let mut step = 0;
+    if keys.just_pressed(KeyCode::Up) || pad(GamepadButtonType::DPadUp){
+        step = -1;
+    }
+    if keys.just_pressed(KeyCode::Down) || pad(GamepadButtonType::DPadDown){
+        step = 1;
+    }
+    let count = query_items.iter().count();
+    if step != 0 && count > 0{
+        let mut index = cursor.index as i32;
+        for _ in 0..count{ // skip over greyed out entries
+            index = (index + step).rem_euclid(count as i32);
+            if query_items.iter().any(|(item, _)| item.index == index as usize && item.enabled){
+                break;
+            }
+        }
+        cursor.index = index as usize;
+    }
+    for (interaction, item) in query_mouse.iter(){
+        match interaction{
+            Interaction::Hovered => cursor.index = item.index,
+            Interaction::Pressed if item.enabled => {
+                cursor.index = item.index;
+                if let Some((_, button)) = query_items.iter().find(|(other, _)| other.index == item.index){
+                    events.send(MenuActivated(*button));
+                }
+            }
+            _ => (),
+        }
+    }
+    if keys.just_pressed(KeyCode::Return) || keys.just_pressed(KeyCode::Space) || pad(GamepadButtonType::South){
+        if let Some((_, button)) = query_items.iter().find(|(item, _)| item.index == cursor.index && item.enabled){
+            events.send(MenuActivated(*button));
+        }
+    }
+    if keys.just_pressed(KeyCode::Escape) || pad(GamepadButtonType::East){
+        match state.get(){
+            AppState::Paused => events.send(MenuActivated(MenuButton::Resume)),
+            AppState::Settings | AppState::Modes | AppState::Stats | AppState::Import | AppState::Analysis | AppState::Network | AppState::Puzzles => events.send(MenuActivated(MenuButton::Back)),
+            _ => (),
+        }
+    }
+}
+
+fn activate_menu_button(
+    mut commands: Commands,
+    mut events: EventReader<MenuActivated>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut settings_return: ResMut<SettingsReturn>,
+    mut settings: ResMut<GameSettings>,
+    mut bindings: ResMut<Bindings>,
+    mut stats: ResMut<LifetimeStats>,
+    mut achievements: ResMut<Achievements>,
+    mut next_game: ResMut<NextGame>,
+    asset_server: Res<AssetServer>,
+    query_screen: Query<Entity, With<MenuScreen>>,
+    mut exit: EventWriter<AppExit>,
+){
+    for MenuActivated(button) in events.iter(){
+        match button{
+            MenuButton::NewGame => next_state.set(AppState::Dealing),
+            MenuButton::Continue | MenuButton::Resume => next_state.set(AppState::Playing),
+            MenuButton::Settings => {
+                settings_return.0 = *state.get();
+                next_state.set(AppState::Settings);
+            }
+            MenuButton::MainMenu => next_state.set(AppState::MainMenu),
+            MenuButton::Back if matches!(state.get(), AppState::Modes | AppState::Stats | AppState::Import | AppState::Network | AppState::Puzzles) => next_state.set(AppState::MainMenu),
+            MenuButton::Back if *state.get() == AppState::Analysis => next_state.set(AppState::GameOver),
+            MenuButton::Back => next_state.set(settings_return.0),
+            MenuButton::Quit => exit.send(AppExit),
+            MenuButton::Modes => next_state.set(AppState::Modes),
+            MenuButton::Stats => next_state.set(AppState::Stats),
+            MenuButton::ImportCode => next_state.set(AppState::Import),
+            MenuButton::Analyse => next_state.set(AppState::Analysis),
+            MenuButton::Network => next_state.set(AppState::Network),
+            MenuButton::Puzzles => next_state.set(AppState::Puzzles),
+            MenuButton::Preset(preset) => {
+                next_game.preset = *preset;
+                next_state.set(AppState::Dealing);
+            }
+            MenuButton::Versus => {
+                let next = Mode::ALL.iter().position(|mode| *mode == next_game.mode).map_or(0, |index| (index + 1) % Mode::ALL.len());
+                next_game.mode = Mode::ALL[next];
+                for entity in query_screen.iter(){
+                    commands.entity(entity).despawn_recursive();
+                }
+                spawn_menu(&mut commands, &asset_server, "Modes", Vec::new(), modes_items(&next_game), Color::BLACK);
+            }
+            MenuButton::Daily => {
+                next_game.daily = true;
+                next_state.set(AppState::Dealing);
+            }
+            MenuButton::ScoreFilter | MenuButton::ExportStats | MenuButton::LoadCode
+                | MenuButton::PreviousTurn | MenuButton::NextTurn | MenuButton::WorstMistake
+                | MenuButton::Host | MenuButton::Join | MenuButton::Watch
+                | MenuButton::Puzzle(_) | MenuButton::Retry | MenuButton::NextPuzzle
+                | MenuButton::FreshPuzzle => (),
+            MenuButton::Setting(setting) => {
+                match setting{
+                    Setting::InputBuffering => settings.input_buffering = !settings.input_buffering,
+                    Setting::AnimationSpeed => {
+                        let next = SPEEDS.iter().position(|speed| *speed > settings.animation_speed).unwrap_or(0);
+                        settings.set_animation_speed(SPEEDS[next]);
+                    }
+                    Setting::InstantAnimations => settings.instant_animations = !settings.instant_animations,
+                    Setting::ReducedMotion => settings.cycle_reduced_motion(),
+                    Setting::MoveLog => settings.show_move_log = !settings.show_move_log,
+                    Setting::HintStrength => {
+                        let next = HINT_STRENGTHS.iter().position(|(strength, _)| *strength > settings.hint_strength).unwrap_or(0);
+                        settings.hint_strength = HINT_STRENGTHS[next].0;
+                    }
+                    Setting::PuzzleDifficulty => {
+                        let next = Difficulty::ALL.iter().position(|difficulty| *difficulty == settings.puzzle_difficulty).map_or(0, |index| (index + 1) % Difficulty::ALL.len());
+                        settings.puzzle_difficulty = Difficulty::ALL[next];
+                    }
+                    Setting::SpectatorStream => settings.spectator_stream = !settings.spectator_stream,
+                    Setting::KeyPreset => {
+                        let preset = bindings.preset.next();
+                        bindings.switch_preset(preset);
+                        bindings.save();
+                    }
+                    Setting::Profile => {
+                        let profiles = storage::profiles(&settings.profile);
+                        let next = profiles.iter().position(|profile| *profile == settings.profile).map_or(0, |index| (index + 1) % profiles.len());
+                        settings.profile = profiles[next].clone();
+                        *stats = LifetimeStats::load(&settings.profile);
+                        *achievements = Achievements::load(&settings.profile);
+                    }
+                }
+                settings.save();
+                for entity in query_screen.iter(){
+                    commands.entity(entity).despawn_recursive();
+                }
+                spawn_menu(&mut commands, &asset_server, "Settings", Vec::new(), settings_items(&settings, &bindings), Color::BLACK);
+            }
+        }
+    }
+}
+
+fn highlight_menu_item(cursor: Res<MenuCursor>, mut query: Query<(&MenuItem, &mut BackgroundColor)>){
+    for (item, mut background) in query.iter_mut(){
+        let color = if item.index == cursor.index { Color::rgb(0.25, 0.25, 0.25) } else { Color::NONE };
+        if background.0 != color{
+            background.0 = color;
+        }
+    }
+}
+
+fn pause_game(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<Bindings>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    mut next_state: ResMut<NextState<AppState>>,
+){
+    let start = gamepads.iter().any(|gamepad| buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::Start)));
+    if bindings.just_pressed(&keys, InputAction::Pause) || start{
+        next_state.set(AppState::Paused);
+    }
+}
+
+fn freeze_tweens(mut query: Query<&mut Animator<Transform>, Without<Toast>>, mut query_sprites: Query<&mut Animator<TextureAtlasSprite>>){
+    for mut animator in query.iter_mut(){
+        animator.state = AnimatorState::Paused;
+    }
+    for mut animator in query_sprites.iter_mut(){
+        animator.state = AnimatorState::Paused;
+    }
+}
+
+fn resume_tweens(mut query: Query<&mut Animator<Transform>>, mut query_sprites: Query<&mut Animator<TextureAtlasSprite>>){
+    for mut animator in query.iter_mut(){
+        animator.state = AnimatorState::Playing;
+    }
+    for mut animator in query_sprites.iter_mut(){
+        animator.state = AnimatorState::Playing;
+    }
+}
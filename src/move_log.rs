@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+use crate::{input::{Bindings, InputAction}, session::Session, Board, GameSettings};
+
+const LOG_LINES: usize = 8; // the most recent turns, older ones scroll off
+
+#[derive(Component)]
+pub struct MoveLog{}
+
+pub fn spawn_move_log(mut commands: Commands, asset_server: Res<AssetServer>){
+    let font = asset_server.load("Play-Regular.ttf");
+    commands.spawn((NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.),
+            right: Val::Px(8.),
+            width: Val::Px(180.),
+            padding: UiRect::all(Val::Px(6.)),
+            ..default()
+        },
+        background_color: Color::rgba(0.1, 0.1, 0.1, 0.7).into(),
+        z_index: ZIndex::Global(-1), // under any menu opened over the board
+        ..default()
+    },
+    Board{},
+    )).with_children(|parent| {
+        parent.spawn((TextBundle::from_section("", TextStyle {
+            font,
+            font_size: 14.0,
+            color: Color::rgb(0.85, 0.85, 0.85),
+        }),
+        MoveLog{},
+        ));
+    });
+}
+
+pub fn toggle_move_log(input: Res<Input<KeyCode>>, bindings: Res<Bindings>, mut settings: ResMut<GameSettings>){
+    if bindings.just_released(&input, InputAction::ToggleMoveLog){
+        settings.show_move_log = !settings.show_move_log;
+        settings.save();
+    }
+}
+
+pub fn update_move_log(
+    session: Res<Session>,
+    settings: Res<GameSettings>,
+    mut query: Query<(&mut Text, &Parent), With<MoveLog>>,
+    mut query_panel: Query<&mut Visibility>,
+){
+    for (mut text, parent) in query.iter_mut(){
+        if let Ok(mut visibility) = query_panel.get_mut(parent.get()){
+            let empty = session.history.is_empty() && session.hint.is_none();
+            let wanted = if settings.show_move_log && !empty { Visibility::Inherited } else { Visibility::Hidden };
+            if *visibility != wanted{
+                *visibility = wanted;
+            }
+        }
+        if !session.is_changed() && !text.sections[0].value.is_empty(){
+            continue;
+        }
+        let first = session.history.len().saturating_sub(LOG_LINES);
+        let mut lines: Vec<String> = session.history.iter().enumerate().skip(first)
+            .map(|(turn, played)| format!("{}. {}", turn + 1, played.notation))
+            .collect();
+        if let Some(hint) = session.hint{
+            lines.push(format!("Hint: {hint}"));
+        }
+        text.sections[0].value = lines.join("\n");
+    }
+}
@@ -0,0 +1,350 @@
+use std::{collections::VecDeque, fmt, io::{self, Read, Write}, net::{TcpListener, TcpStream, ToSocketAddrs}, time::Duration};
+use serde::{Deserialize, Serialize};
+use crate::rules::{Game, Mode, Move, MoveError, RulePreset, HAND_SIZE, WORLDS};
+
+// Two copies of the game kept in lockstep over TCP. The host picks the rules and seed, both
+// sides deal the same game from them, and from then on only moves cross the wire, one JSON
+// object per line, each with a hash of the game after it so that any divergence is caught
+// on the turn it happens.
+
+pub const NET_VERSION: u32 = 1;
+pub const DEFAULT_PORT: u16 = 7878;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const MAX_LINE: usize = 4096; // nothing legitimate comes close
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "msg", rename_all = "snake_case")]
+pub enum Message{
+    Hello{ version: u32, preset: RulePreset, mode: Mode, seed: u32 }, // host to guest, once connected
+    Ready{ version: u32 }, // guest to host, the game can start
+    Move{ turn: u16, mv: Move, hash: u64 }, // turn counts from 0, hash is of the game after mv
+    Bye, // leaving
+}
+
+#[derive(Debug)]
+pub enum NetError{
+    Io(io::Error),
+    Disconnected,
+    Garbled(String),
+    Version(u32),
+    Unexpected(&'static str),
+    BadMove(MoveError),
+    Desync(u16),
+}
+
+impl fmt::Display for NetError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            NetError::Io(err) => write!(f, "{err}"),
+            NetError::Disconnected => write!(f, "the other player left"),
+            NetError::Garbled(line) => write!(f, "could not understand the other side: {line}"),
+            NetError::Version(version) => write!(f, "the other side runs another version (network format {version})"),
+            NetError::Unexpected(what) => write!(f, "the other side sent {what} at the wrong time"),
+            NetError::BadMove(err) => write!(f, "the other side played an illegal move: {err}"),
+            NetError::Desync(turn) => write!(f, "the games went out of sync on turn {}", turn + 1),
+        }
+    }
+}
+
+impl std::error::Error for NetError{}
+
+impl From<io::Error> for NetError{
+    fn from(err: io::Error) -> Self{
+        NetError::Io(err)
+    }
+}
+
+// FNV-1a over everything either side could disagree on, including the order of the deck.
+pub fn state_hash(game: &Game) -> u64{
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    let mut add = |byte: u8| {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    };
+    game.polarity.iter().chain(game.hands.iter().flatten()).chain(game.deck.iter()).for_each(|value| add(*value as u8));
+    [game.score, game.turn, game.scores[0], game.scores[1]].iter().for_each(|value| value.to_be_bytes().into_iter().for_each(&mut add));
+    add(game.pleroma as u8);
+    add(game.player);
+    hash
+}
+
+// What the guest makes of the host's first message: the deal, or why it cannot play it.
+pub fn expect_hello(message: Message) -> Result<(RulePreset, Mode, u32), NetError>{
+    match message{
+        Message::Hello{ version, preset, mode, seed } if version == NET_VERSION => Ok((preset, mode, seed)),
+        Message::Hello{ version, .. } => Err(NetError::Version(version)),
+        other => Err(out_of_turn(other)),
+    }
+}
+
+// What the host makes of the guest's answer to its Hello.
+pub fn expect_ready(message: Message) -> Result<(), NetError>{
+    match message{
+        Message::Ready{ version } if version == NET_VERSION => Ok(()),
+        Message::Ready{ version } => Err(NetError::Version(version)),
+        other => Err(out_of_turn(other)),
+    }
+}
+
+fn out_of_turn(message: Message) -> NetError{
+    match message{
+        Message::Bye => NetError::Disconnected,
+        Message::Move{ .. } => NetError::Unexpected("a move"),
+        Message::Hello{ .. } | Message::Ready{ .. } => NetError::Unexpected("a new game"),
+    }
+}
+
+// A TCP stream carrying one Message per line. Never blocks on reading.
+pub struct Connection{
+    stream: TcpStream,
+    buffer: Vec<u8>,
+}
+
+impl Connection{
+    fn new(stream: TcpStream) -> io::Result<Self>{
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        Ok(Connection{ stream, buffer: Vec::new() })
+    }
+
+    pub fn connect(address: &str) -> Result<Self, NetError>{
+        let address = if address.contains(':') { address.to_owned() } else { format!("{address}:{DEFAULT_PORT}") };
+        let mut last_error = io::Error::new(io::ErrorKind::NotFound, "no such address");
+        for resolved in address.to_socket_addrs()?{
+            match TcpStream::connect_timeout(&resolved, CONNECT_TIMEOUT){
+                Ok(stream) => return Ok(Connection::new(stream)?),
+                Err(err) => last_error = err,
+            }
+        }
+        Err(last_error.into())
+    }
+
+    pub fn send(&mut self, message: &Message) -> Result<(), NetError>{
+        let mut line = serde_json::to_vec(message).expect("messages always serialize");
+        line.push(b'\n');
+        let mut written = 0;
+        while written < line.len(){
+            match self.stream.write(&line[written..]){
+                Ok(0) => return Err(NetError::Disconnected),
+                Ok(count) => written += count,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => std::thread::yield_now(),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+
+    // The next complete message, if one has arrived.
+    pub fn poll(&mut self) -> Result<Option<Message>, NetError>{
+        loop{
+            if let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n'){
+                let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                let text = String::from_utf8_lossy(&line);
+                return serde_json::from_str(text.trim()).map(Some).map_err(|_| NetError::Garbled(text.trim().to_owned()));
+            }
+            if self.buffer.len() > MAX_LINE{
+                return Err(NetError::Garbled("an overlong line".to_owned()));
+            }
+            let mut chunk = [0; 512];
+            match self.stream.read(&mut chunk){
+                Ok(0) => return Err(NetError::Disconnected),
+                Ok(count) => self.buffer.extend_from_slice(&chunk[..count]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+// Waits for a guest without blocking.
+pub struct Host{
+    listener: TcpListener,
+}
+
+impl Host{
+    pub fn listen(port: u16) -> Result<Self, NetError>{
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Host{ listener })
+    }
+
+    pub fn port(&self) -> u16{
+        self.listener.local_addr().map_or(0, |address| address.port())
+    }
+
+    pub fn accept(&self) -> Result<Option<Connection>, NetError>{
+        match self.listener.accept(){
+            Ok((stream, _)) => Ok(Some(Connection::new(stream)?)),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+// One side of a game in progress. Every move played locally, whoever made it, is passed to
+// record in order: local moves are sent on, remote ones checked against the hash that came
+// with them. Remote moves wait in incoming until the caller plays them.
+pub struct Lockstep{
+    pub connection: Connection,
+    pub local: u8, // the player this side controls, 0 for the host
+    shadow: Game, // the game as of the last recorded move
+    pub incoming: VecDeque<Move>,
+    hashes: VecDeque<(u16, u64)>, // the turn and hash announced with each incoming move
+    gone: bool, // the other side left, possibly after sending moves not played yet
+}
+
+impl Lockstep{
+    pub fn new(connection: Connection, local: u8, game: Game) -> Self{
+        Lockstep{ connection, local, shadow: game, incoming: VecDeque::new(), hashes: VecDeque::new(), gone: false }
+    }
+
+    pub fn local_turn(&self, game: &Game) -> bool{
+        game.player == self.local
+    }
+
+    pub fn recorded_turns(&self) -> u16{
+        self.shadow.turn
+    }
+
+    // Fails once the other side is gone and every move it sent has been played.
+    pub fn poll(&mut self) -> Result<(), NetError>{
+        while !self.gone{
+            let message = match self.connection.poll(){
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(NetError::Disconnected) => Message::Bye,
+                Err(err) => return Err(err),
+            };
+            match message{
+                Message::Move{ turn, mv, hash } => {
+                    if mv.slot() as usize >= HAND_SIZE{
+                        return Err(NetError::BadMove(MoveError::NoSuchSlot(mv.slot())));
+                    }
+                    if let Move::Push{ world, .. } = mv{
+                        if world as usize >= WORLDS{
+                            return Err(NetError::BadMove(MoveError::NoSuchWorld(world)));
+                        }
+                    }
+                    self.incoming.push_back(mv);
+                    self.hashes.push_back((turn, hash));
+                }
+                Message::Bye => self.gone = true,
+                other => return Err(out_of_turn(other)),
+            }
+        }
+        if self.gone && self.incoming.is_empty(){
+            return Err(NetError::Disconnected);
+        }
+        Ok(())
+    }
+
+    pub fn record(&mut self, mv: Move) -> Result<(), NetError>{
+        let turn = self.shadow.turn;
+        let mover = self.shadow.player;
+        self.shadow.apply(mv).map_err(NetError::BadMove)?;
+        let hash = state_hash(&self.shadow);
+        if mover == self.local{
+            return self.connection.send(&Message::Move{ turn, mv, hash });
+        }
+        match self.hashes.pop_front(){
+            Some((announced, expected)) if announced == turn && expected == hash => Ok(()),
+            Some(_) => Err(NetError::Desync(turn)),
+            None => Err(NetError::Unexpected("no move")),
+        }
+    }
+
+    pub fn leave(&mut self){
+        let _ = self.connection.send(&Message::Bye);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use std::{thread, time::Instant};
+    use crate::bot::{Bot, GreedyBot};
+    use super::*;
+
+    const SEED: u32 = 42;
+
+    // Polls until something arrives, so that a test fails instead of hanging if nothing does.
+    fn wait<T>(mut poll: impl FnMut() -> Result<Option<T>, NetError>) -> T{
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop{
+            if let Some(value) = poll().unwrap(){
+                return value;
+            }
+            assert!(Instant::now() < deadline, "nothing arrived");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    // Both sides of the handshake on localhost: the host's connection, and the guest's
+    // connection with the deal it was sent.
+    fn connect(mode: Mode) -> (Connection, thread::JoinHandle<(Connection, Game)>){
+        let host = Host::listen(0).unwrap();
+        let port = host.port();
+        let guest = thread::spawn(move || {
+            let mut connection = Connection::connect(&format!("127.0.0.1:{port}")).unwrap();
+            let (preset, mode, seed) = expect_hello(wait(|| connection.poll())).unwrap();
+            connection.send(&Message::Ready{ version: NET_VERSION }).unwrap();
+            (connection, Game::deal(preset.rules(), seed, mode))
+        });
+        let mut connection = wait(|| host.accept());
+        connection.send(&Message::Hello{ version: NET_VERSION, preset: RulePreset::Classic, mode, seed: SEED }).unwrap();
+        expect_ready(wait(|| connection.poll())).unwrap();
+        (connection, guest)
+    }
+
+    // Plays one side to the end with a bot, as the board would: every move goes through record.
+    // Returns the hash of the game after each turn.
+    fn play_side(connection: Connection, local: u8, mut game: Game) -> Result<Vec<u64>, NetError>{
+        let mut lockstep = Lockstep::new(connection, local, game.clone());
+        let mut bot = GreedyBot::new(local as u64);
+        let mut hashes = Vec::new();
+        while !game.is_over(){
+            let mv = if lockstep.local_turn(&game){
+                bot.choose(&game)
+            }
+            else {
+                wait(|| lockstep.poll().map(|_| lockstep.incoming.pop_front()))
+            };
+            game.apply(mv).map_err(NetError::BadMove)?;
+            lockstep.record(mv)?;
+            hashes.push(state_hash(&game));
+        }
+        lockstep.leave();
+        Ok(hashes)
+    }
+
+    #[test]
+    fn both_sides_stay_in_step_to_the_end(){
+        let (connection, guest) = connect(Mode::Hotseat);
+        let game = Game::deal(RulePreset::Classic.rules(), SEED, Mode::Hotseat);
+        let guest = thread::spawn(move || {
+            let (connection, game) = guest.join().unwrap();
+            play_side(connection, 1, game).unwrap()
+        });
+        let host_hashes = play_side(connection, 0, game.clone()).unwrap();
+        let guest_hashes = guest.join().unwrap();
+        let mut finished = game; // a deal lasts as many turns however it is played
+        while !finished.is_over(){
+            finished.apply(finished.legal_moves()[0]).unwrap();
+        }
+        assert_eq!(host_hashes.len(), finished.turn as usize);
+        assert_eq!(host_hashes, guest_hashes);
+    }
+
+    #[test]
+    fn a_corrupted_side_is_caught_on_the_next_move(){
+        let (connection, guest) = connect(Mode::Hotseat);
+        let game = Game::deal(RulePreset::Classic.rules(), SEED, Mode::Hotseat);
+        let guest = thread::spawn(move || {
+            let (connection, mut game) = guest.join().unwrap();
+            game.polarity[0] += 1; // the guest's copy no longer matches the host's
+            play_side(connection, 1, game)
+        });
+        let mut lockstep = Lockstep::new(connection, 0, game.clone());
+        lockstep.record(GreedyBot::new(0).choose(&game)).unwrap();
+        assert!(matches!(guest.join().unwrap(), Err(NetError::Desync(0))));
+    }
+}
@@ -0,0 +1,378 @@
+use bevy::{prelude::*, tasks::{AsyncComputeTaskPool, Task, futures_lite::future}};
+use pleromakenoma::{
+    net::{self, Connection, Host, Lockstep, Message, NetError, DEFAULT_PORT, NET_VERSION},
+    rules::{Game, Mode},
+    spectate::{Frame, SpectateError, Spectator},
+};
+use crate::{input::InputQueue, menu::{spawn_menu, AppState, MenuActivated, MenuButton, MenuCursor, MenuScreen}, session::{NextGame, Session}, versus, Board};
+
+// A versus game against another copy of the game over TCP. The host deals, both sides play
+// the same seed and only moves are exchanged from then on, see pleromakenoma::net.
+// Watching follows a game published with the spectator stream setting, read-only.
+
+enum Link{
+    Listening(Host),
+    Joining(Task<Result<Connection, NetError>>), // connecting can take seconds on a bad address
+    Greeting(Connection, Message), // the host sent its Hello and waits for Ready
+    Awaiting(Connection), // the guest waits for the host's Hello
+    Watching(Task<Result<Spectator, SpectateError>>),
+    Tuning(Spectator), // connected, the game comes next
+}
+
+enum Agreed{
+    Play(Connection, u8), // the player this side controls
+    Watch(Spectator, Game), // the game as the stream has it
+}
+
+#[derive(Resource, Default)]
+pub struct Network{
+    address: String, // typed on the network screen
+    link: Option<Link>,
+    agreed: Option<Agreed>, // ready for the deal
+    lockstep: Option<Lockstep>,
+    spectator: Option<(Spectator, Game)>,
+    status: Option<String>, // what the network screen is doing, or why a game stopped
+}
+
+#[derive(Component)]
+pub struct NetworkStatus{}
+
+// Solitaire has no one to play against, so the host falls back to taking turns.
+fn hosted_mode(mode: Mode) -> Mode{
+    if mode.is_versus() { mode } else { Mode::Hotseat }
+}
+
+fn network_lines(network: &Network) -> Vec<String>{
+    let mut lines = vec![
+        format!("Host a game on port {DEFAULT_PORT}, or type the host's address to join or watch"),
+        format!("> {}_", network.address),
+    ];
+    lines.extend(network.status.clone());
+    lines.push(String::new());
+    lines
+}
+
+fn network_items(network: &Network, next_game: &NextGame) -> Vec<(MenuButton, String, bool)>{
+    let idle = network.link.is_none();
+    vec![
+        (MenuButton::Host, format!("Host: {}, {}", next_game.preset.name(), hosted_mode(next_game.mode).name()), idle),
+        (MenuButton::Join, "Join".to_owned(), idle && !network.address.is_empty()),
+        (MenuButton::Watch, "Watch".to_owned(), idle && !network.address.is_empty()),
+        (MenuButton::Back, "Back".to_owned(), true),
+    ]
+}
+
+pub fn spawn_network(mut commands: Commands, asset_server: Res<AssetServer>, network: Res<Network>, next_game: Res<NextGame>, mut cursor: ResMut<MenuCursor>){
+    cursor.index = 0;
+    spawn_menu(&mut commands, &asset_server, "Network", network_lines(&network), network_items(&network, &next_game), Color::BLACK);
+}
+
+pub fn step_network(
+    mut commands: Commands,
+    mut characters: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut events: EventReader<MenuActivated>,
+    asset_server: Res<AssetServer>,
+    mut network: ResMut<Network>,
+    mut next_game: ResMut<NextGame>,
+    mut next_state: ResMut<NextState<AppState>>,
+    query_screen: Query<Entity, With<MenuScreen>>,
+){
+    let mut changed = false;
+    for typed in characters.iter(){
+        if network.link.is_none() && (typed.char.is_ascii_alphanumeric() || ".:-[]".contains(typed.char)){
+            network.address.push(typed.char);
+            changed = true;
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) && network.link.is_none(){
+        changed |= network.address.pop().is_some();
+    }
+    for MenuActivated(button) in events.iter(){
+        match button{
+            MenuButton::Host => network.status = Some(match Host::listen(DEFAULT_PORT){
+                Ok(host) => {
+                    network.link = Some(Link::Listening(host));
+                    format!("Waiting for a player on port {DEFAULT_PORT}...")
+                }
+                Err(err) => format!("Cannot host: {err}"),
+            }),
+            MenuButton::Join => {
+                let address = network.address.clone();
+                network.link = Some(Link::Joining(AsyncComputeTaskPool::get().spawn(async move { Connection::connect(&address) })));
+                network.status = Some(format!("Connecting to {address}..."));
+            }
+            MenuButton::Watch => {
+                let address = network.address.clone();
+                network.link = Some(Link::Watching(AsyncComputeTaskPool::get().spawn(async move { Spectator::connect(&address) })));
+                network.status = Some(format!("Connecting to the stream at {address}..."));
+            }
+            _ => continue,
+        }
+        changed = true;
+    }
+
+    let status = match network.link.take(){
+        Some(Link::Listening(host)) => match host.accept(){
+            Ok(Some(mut connection)) => {
+                let hello = Message::Hello{ version: NET_VERSION, preset: next_game.preset, mode: hosted_mode(next_game.mode), seed: rand::random() };
+                connection.send(&hello).map_err(|err| err.to_string()).map(|_| {
+                    network.link = Some(Link::Greeting(connection, hello));
+                    Some("A player joined, dealing...".to_owned())
+                })
+            }
+            Ok(None) => {
+                network.link = Some(Link::Listening(host));
+                Ok(None)
+            }
+            Err(err) => Err(err.to_string()),
+        },
+        Some(Link::Joining(mut task)) => match future::block_on(future::poll_once(&mut task)){
+            Some(Ok(connection)) => {
+                network.link = Some(Link::Awaiting(connection));
+                Ok(Some("Connected, waiting for the host's game...".to_owned()))
+            }
+            Some(Err(err)) => Err(err.to_string()),
+            None => {
+                network.link = Some(Link::Joining(task));
+                Ok(None)
+            }
+        },
+        Some(Link::Greeting(mut connection, hello)) => match connection.poll(){
+            Ok(Some(message)) => net::expect_ready(message).map_err(|err| err.to_string()).map(|_| {
+                network.agreed = Some(Agreed::Play(connection, 0));
+                deal(&mut next_game, hello);
+                None
+            }),
+            Ok(None) => {
+                network.link = Some(Link::Greeting(connection, hello));
+                Ok(None)
+            }
+            Err(err) => Err(err.to_string()),
+        },
+        Some(Link::Awaiting(mut connection)) => match connection.poll(){
+            Ok(Some(hello)) => net::expect_hello(hello).and_then(|_| connection.send(&Message::Ready{ version: NET_VERSION })).map_err(|err| err.to_string()).map(|_| {
+                network.agreed = Some(Agreed::Play(connection, 1));
+                deal(&mut next_game, hello);
+                None
+            }),
+            Ok(None) => {
+                network.link = Some(Link::Awaiting(connection));
+                Ok(None)
+            }
+            Err(err) => Err(err.to_string()),
+        },
+        Some(Link::Watching(mut task)) => match future::block_on(future::poll_once(&mut task)){
+            Some(Ok(spectator)) => {
+                network.link = Some(Link::Tuning(spectator));
+                Ok(Some("Connected, waiting for the game...".to_owned()))
+            }
+            Some(Err(err)) => Err(err.to_string()),
+            None => {
+                network.link = Some(Link::Watching(task));
+                Ok(None)
+            }
+        },
+        Some(Link::Tuning(mut spectator)) => match spectator.poll(){
+            Ok(Some(frame @ Frame::Game{ .. })) => follow(&mut next_game, frame).map(|game| {
+                network.agreed = Some(Agreed::Watch(spectator, game));
+                None
+            }),
+            Ok(_) => {
+                network.link = Some(Link::Tuning(spectator));
+                Ok(None)
+            }
+            Err(err) => Err(err.to_string()),
+        },
+        None => Ok(None),
+    };
+    match status{
+        Ok(Some(status)) => {
+            network.status = Some(status);
+            changed = true;
+        }
+        Ok(None) => (),
+        Err(err) => {
+            network.status = Some(format!("Cannot connect: {err}"));
+            changed = true;
+        }
+    }
+    if network.agreed.is_some(){
+        network.status = None;
+        next_state.set(AppState::Dealing);
+        return;
+    }
+    if !changed{
+        return;
+    }
+    for entity in query_screen.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_menu(&mut commands, &asset_server, "Network", network_lines(&network), network_items(&network, &next_game), Color::BLACK);
+}
+
+fn deal(next_game: &mut NextGame, hello: Message){
+    let Message::Hello{ preset, mode, seed, .. } = hello else { return };
+    next_game.preset = preset;
+    next_game.mode = mode;
+    next_game.seed = Some(seed);
+    next_game.daily = false;
+    next_game.moves.clear();
+}
+
+// Deals the game a stream showed, to be replayed up to where the stream is. Returns that game,
+// or why the board could not play it.
+fn follow(next_game: &mut NextGame, frame: Frame) -> Result<Game, String>{
+    let Frame::Game{ state, moves } = frame else { return Err("a move before the game".to_owned()) };
+    let preset = state.preset.ok_or("the stream has custom rules")?;
+    let mut game = Game::deal(preset.rules(), state.seed, state.mode);
+    for mv in moves.iter(){
+        game.apply(*mv).map_err(|err| err.to_string())?;
+    }
+    next_game.preset = preset;
+    next_game.mode = state.mode;
+    next_game.seed = Some(state.seed);
+    next_game.daily = false;
+    next_game.moves = moves;
+    Ok(game)
+}
+
+// Hangs up, on the way back to the main menu.
+pub fn disconnect(mut network: ResMut<Network>){
+    if let Some(lockstep) = network.lockstep.as_mut(){
+        lockstep.leave();
+    }
+    network.link = None;
+    network.agreed = None;
+    network.lockstep = None;
+    network.spectator = None;
+    network.status = None;
+}
+
+// Ties the game just dealt to the connection, if the deal came from the network screen or
+// the stream being watched. Any other deal ends the network game in progress.
+pub fn attach_lockstep(mut network: ResMut<Network>, session: Res<Session>){
+    if let Some(mut lockstep) = network.lockstep.take(){
+        lockstep.leave();
+    }
+    network.spectator = None;
+    network.status = None;
+    match network.agreed.take(){
+        Some(Agreed::Play(connection, local)) => network.lockstep = Some(Lockstep::new(connection, local, session.game.clone())),
+        Some(Agreed::Watch(spectator, game)) => network.spectator = Some((spectator, game)),
+        None => (),
+    }
+}
+
+pub fn spawn_network_status(mut commands: Commands, asset_server: Res<AssetServer>, network: Res<Network>, session: Res<Session>){
+    let status = match (&network.lockstep, &network.spectator){
+        (Some(lockstep), _) => format!("Online as {}", versus::player_name(session.game.mode, lockstep.local)),
+        (None, Some(_)) => "Watching".to_owned(),
+        (None, None) => return,
+    };
+    commands.spawn((NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(8.),
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        z_index: ZIndex::Global(-1), // under any menu opened over the board
+        ..default()
+    },
+    Board{},
+    )).with_children(|parent| {
+        parent.spawn((TextBundle::from_section(status, TextStyle {
+            font: asset_server.load("Play-Regular.ttf"),
+            font_size: 14.,
+            color: Color::rgb(0.45, 0.45, 0.45),
+        }), NetworkStatus{}));
+    });
+}
+
+// Sends the moves played here, plays the ones that came in, and checks every turn against
+// the other side's hash. Local input is dropped while it is the other player's turn.
+pub fn sync_lockstep(
+    mut network: ResMut<Network>,
+    mut session: ResMut<Session>,
+    mut queue: ResMut<InputQueue>,
+    mut query_status: Query<&mut Text, With<NetworkStatus>>,
+){
+    let network = &mut *network;
+    let Some(lockstep) = network.lockstep.as_mut() else { return };
+    if network.status.is_some(){ // the game cannot go on without the other side
+        queue.clear();
+        return;
+    }
+    let result = sync(lockstep, &mut session);
+    if !lockstep.local_turn(&session.game){
+        queue.clear();
+    }
+    if let Err(err) = result{
+        lockstep.leave();
+        network.status = Some(stopped(err, &mut query_status));
+    }
+}
+
+fn stopped(err: impl std::fmt::Display, query_status: &mut Query<&mut Text, With<NetworkStatus>>) -> String{
+    let status = format!("Stopped: {err}. Escape for the menu");
+    for mut text in query_status.iter_mut(){
+        text.sections[0].value = status.clone();
+        text.sections[0].style.color = Color::rgb(0.9, 0.4, 0.4);
+    }
+    status
+}
+
+// Mirrors the stream being watched: its moves are played out on the board as they come, and
+// a new deal there deals here too. Nothing is played from this side.
+pub fn follow_stream(
+    mut network: ResMut<Network>,
+    mut session: ResMut<Session>,
+    mut queue: ResMut<InputQueue>,
+    mut next_game: ResMut<NextGame>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut query_status: Query<&mut Text, With<NetworkStatus>>,
+){
+    let network = &mut *network;
+    let Some((spectator, game)) = network.spectator.as_mut() else { return };
+    queue.clear();
+    if network.status.is_some(){
+        return;
+    }
+    let problem = loop{
+        match spectator.poll(){
+            Ok(Some(Frame::Move{ mv, .. })) => match game.apply(mv){
+                Ok(_) => session.replay.push_back(mv),
+                Err(err) => break err.to_string(),
+            },
+            Ok(Some(frame @ Frame::Game{ .. })) => match follow(&mut next_game, frame){
+                Ok(dealt) => {
+                    network.agreed = network.spectator.take().map(|(spectator, _)| Agreed::Watch(spectator, dealt));
+                    next_state.set(AppState::Dealing);
+                    return;
+                }
+                Err(err) => break err,
+            },
+            Ok(None) => return,
+            Err(err) => break err.to_string(),
+        }
+    };
+    network.status = Some(stopped(problem, &mut query_status));
+}
+
+fn sync(lockstep: &mut Lockstep, session: &mut ResMut<Session>) -> Result<(), NetError>{
+    for turn in session.history.iter().skip(lockstep.recorded_turns() as usize){
+        lockstep.record(turn.mv)?;
+    }
+    if session.game.is_over(){ // the other side may leave now, all its moves are in
+        return Ok(());
+    }
+    lockstep.poll()?;
+    if !lockstep.local_turn(&session.game) && session.replay.is_empty(){
+        if let Some(mv) = lockstep.incoming.pop_front(){
+            session.replay.push_back(mv);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use std::{fmt, str::FromStr};
+use crate::rules::{Game, Move, Outcome, HAND_SIZE, WORLDS};
+
+// One turn as text. Slots and worlds count from 1, like the keys that play them.
+//
+//   K 3→W2        Kenoma plays hand slot 3 onto world 2
+//   K 3:5→W2 =-1  the same, noting the card (5) and the world's polarity afterwards
+//   P 1→claim     Pleroma claims with hand slot 1
+//   P 1:4→claim +2  noting the card and how many worlds were balanced
+//
+// "->" may stand in for the arrow, spaces around the arrow and the colon are allowed
+// ("K 3 → W2") and letter case does not matter.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Notation{
+    pub pleroma: bool,
+    pub mv: Move,
+    pub card: Option<i8>,
+    pub result: Option<i8>, // the polarity after a push, or the worlds balanced by a claim
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum NotationError{
+    Dimension(String),
+    Slot(String),
+    Card(String),
+    Target(String),
+    Result(String),
+    Missing(&'static str),
+    WrongDimension,
+    WrongCard{ slot: u8, card: i8 }, // what the slot actually holds
+}
+
+impl fmt::Display for NotationError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            NotationError::Dimension(text) => write!(f, "'{text}' is not a dimension, expected K or P"),
+            NotationError::Slot(text) => write!(f, "'{text}' is not a hand slot, expected 1 to {HAND_SIZE}"),
+            NotationError::Card(text) => write!(f, "'{text}' is not a card value"),
+            NotationError::Target(text) => write!(f, "'{text}' is not a target, expected W1 to W{WORLDS} or claim"),
+            NotationError::Result(text) => write!(f, "'{text}' is not a result, expected =polarity or +worlds"),
+            NotationError::Missing(part) => write!(f, "the {part} is missing"),
+            NotationError::WrongDimension => write!(f, "it is the other dimension's turn"),
+            NotationError::WrongCard{ slot, card } => write!(f, "hand slot {} holds a {card}", slot + 1),
+        }
+    }
+}
+
+impl std::error::Error for NotationError{}
+
+impl Notation{
+    // Describes a move that was just applied to game.
+    pub fn new(pleroma: bool, mv: Move, outcome: &Outcome, game: &Game) -> Self{
+        let result = match mv{
+            Move::Push{ world, .. } => game.polarity[world as usize],
+            Move::Claim{ .. } => outcome.balanced.len() as i8,
+        };
+        Notation{ pleroma, mv, card: Some(outcome.card), result: Some(result) }
+    }
+
+    // The move, once it is known to be for the dimension whose turn it is in game and, when
+    // the card is noted, to play the card in that slot.
+    pub fn move_for(&self, game: &Game) -> Result<Move, NotationError>{
+        if self.pleroma != game.pleroma{
+            return Err(NotationError::WrongDimension);
+        }
+        let slot = self.mv.slot();
+        let held = game.hand()[slot as usize];
+        if self.card.is_some_and(|card| card != held){
+            return Err(NotationError::WrongCard{ slot, card: held });
+        }
+        Ok(self.mv)
+    }
+}
+
+impl fmt::Display for Notation{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{} {}", if self.pleroma { "P" } else { "K" }, self.mv.slot() + 1)?;
+        if let Some(card) = self.card{
+            write!(f, ":{card}")?;
+        }
+        match self.mv{
+            Move::Push{ world, .. } => write!(f, "→W{}", world + 1)?,
+            Move::Claim{ .. } => write!(f, "→claim")?,
+        }
+        match (self.mv, self.result){
+            (Move::Push{ .. }, Some(polarity)) => write!(f, " ={polarity}"),
+            (Move::Claim{ .. }, Some(balanced)) => write!(f, " +{balanced}"),
+            (_, None) => Ok(()),
+        }
+    }
+}
+
+fn number(text: &str, min: i8, max: i8) -> Option<i8>{
+    text.parse::<i8>().ok().filter(|n| (min..=max).contains(n))
+}
+
+impl FromStr for Notation{
+    type Err = NotationError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err>{
+        let text = text.trim().replace("->", "→");
+        let text = text.split('→').map(str::trim).collect::<Vec<_>>().join("→");
+        let text = text.split(':').map(str::trim).collect::<Vec<_>>().join(":");
+        let mut parts = text.split_whitespace();
+        let dimension = parts.next().ok_or(NotationError::Missing("dimension"))?;
+        let pleroma = match dimension.to_ascii_uppercase().as_str(){
+            "K" => false,
+            "P" => true,
+            _ => return Err(NotationError::Dimension(dimension.to_owned())),
+        };
+        let play = parts.next().ok_or(NotationError::Missing("hand slot"))?;
+        let (hand, target) = play.split_once('→').ok_or(NotationError::Missing("target"))?;
+        let (slot, card) = match hand.split_once(':'){
+            Some((slot, card)) => (slot, Some(card)),
+            None => (hand, None),
+        };
+        let slot = number(slot, 1, HAND_SIZE as i8).ok_or_else(|| NotationError::Slot(slot.to_owned()))? as u8 - 1;
+        let card = match card{
+            Some(card) => Some(number(card, 1, i8::MAX).ok_or_else(|| NotationError::Card(card.to_owned()))?),
+            None => None,
+        };
+        let lower = target.to_ascii_lowercase();
+        let mv = if lower == "claim"{
+            Move::Claim{ slot }
+        } else {
+            let world = lower.strip_prefix('w').and_then(|world| number(world, 1, WORLDS as i8))
+                .ok_or_else(|| NotationError::Target(target.to_owned()))?;
+            Move::Push{ slot, world: world as u8 - 1 }
+        };
+        let result = match parts.next(){
+            Some(result) => {
+                let value = match mv{
+                    Move::Push{ .. } => result.strip_prefix('='),
+                    Move::Claim{ .. } => result.strip_prefix('+'),
+                };
+                Some(value.and_then(|value| value.parse().ok()).ok_or_else(|| NotationError::Result(result.to_owned()))?)
+            }
+            None => None,
+        };
+        if let Some(extra) = parts.next(){
+            return Err(NotationError::Result(extra.to_owned()));
+        }
+        Ok(Notation{ pleroma, mv, card, result })
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::rules::RulePreset;
+
+    fn parse(text: &str) -> Result<Notation, NotationError>{
+        text.parse()
+    }
+
+    #[test]
+    fn printed_turns_parse_back_to_the_same_move(){
+        let mut game = Game::new(RulePreset::Classic.rules(), 11);
+        let mut claims = 0;
+        while !game.is_over(){
+            // Every slot and world in turn, with a claim every fifth move to cross over.
+            let slot = (game.turn % HAND_SIZE as u16) as u8;
+            let mv = match game.turn % 5{
+                4 => Move::Claim{ slot },
+                world => Move::Push{ slot, world: world as u8 },
+            };
+            let before = game.clone();
+            let outcome = game.apply(mv).unwrap();
+            let notation = Notation::new(before.pleroma, mv, &outcome, &game);
+            let parsed = parse(&notation.to_string()).unwrap();
+            assert_eq!(parsed, notation);
+            assert_eq!(parsed.move_for(&before), Ok(mv));
+            let short = Notation{ card: None, result: None, ..notation };
+            assert_eq!(parse(&short.to_string()).and_then(|short| short.move_for(&before)), Ok(mv));
+            claims += matches!(mv, Move::Claim{ .. }) as u32;
+        }
+        assert!(claims > 0 && game.turn > claims as u16);
+    }
+
+    #[test]
+    fn spaces_ascii_arrows_and_case_are_accepted(){
+        let push = Notation{ pleroma: false, mv: Move::Push{ slot: 2, world: 1 }, card: None, result: None };
+        for text in ["K 3→W2", "K 3 → W2", "k 3 -> w2", "  K 3->W2  ", "K 3 ->W2"]{
+            assert_eq!(parse(text), Ok(push), "{text}");
+        }
+        let claim = Notation{ pleroma: true, mv: Move::Claim{ slot: 0 }, card: Some(4), result: Some(2) };
+        for text in ["P 1:4→claim +2", "P 1 : 4 → Claim +2", "p 1:4 -> CLAIM +2"]{
+            assert_eq!(parse(text), Ok(claim), "{text}");
+        }
+        assert_eq!(parse("K 3:5 → W2 =-1").map(|notation| notation.result), Ok(Some(-1)));
+    }
+
+    #[test]
+    fn malformed_turns_say_what_is_wrong(){
+        assert_eq!(parse(""), Err(NotationError::Missing("dimension")));
+        assert_eq!(parse("X 1→W1"), Err(NotationError::Dimension("X".to_owned())));
+        assert_eq!(parse("K"), Err(NotationError::Missing("hand slot")));
+        assert_eq!(parse("K 1"), Err(NotationError::Missing("target")));
+        assert_eq!(parse("K 5→W1"), Err(NotationError::Slot("5".to_owned())));
+        assert_eq!(parse("K 1:0→W1"), Err(NotationError::Card("0".to_owned())));
+        assert_eq!(parse("K 1→W5"), Err(NotationError::Target("W5".to_owned())));
+        assert_eq!(parse("K 1→W1 +2"), Err(NotationError::Result("+2".to_owned())));
+        assert_eq!(parse("K 1→claim +2 x"), Err(NotationError::Result("x".to_owned())));
+    }
+
+    #[test]
+    fn moves_must_fit_the_position(){
+        let game = Game::from_parts(RulePreset::Classic.rules(), [2, 2, 2, 2], [1, 2, 3, 4], vec![1]);
+        assert_eq!(parse("P 1→W1").unwrap().move_for(&game), Err(NotationError::WrongDimension));
+        assert_eq!(parse("K 2:5→W1").unwrap().move_for(&game), Err(NotationError::WrongCard{ slot: 1, card: 2 }));
+        assert_eq!(parse("K 2:2→W1").unwrap().move_for(&game), Ok(Move::Push{ slot: 1, world: 0 }));
+    }
+}
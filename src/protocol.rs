@@ -0,0 +1,359 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::{notation::Notation, rules::{Game, Mode, Move, MoveError, Outcome, RulePreset, Rules}};
+
+// Remote control of a game through one JSON object per line, for programs playing headlessly.
+// Every command gets exactly one reply line. Slots and worlds count from 0.
+//
+//   {"cmd":"new","preset":"Classic","seed":7}   deal, any field may be left out; "rules" takes
+//                                               a full Rules object instead of a preset
+//   {"cmd":"state"}
+//   {"cmd":"play","slot":2,"world":1}           push a world with a card
+//   {"cmd":"claim","slot":0}
+//   {"cmd":"move","notation":"K 3→W2"}          either of the above in move notation, see
+//                                               notation.rs, where slots and worlds count from 1
+//   {"cmd":"undo"}                              take back the last play or claim
+//
+//   {"ok":true,"events":[...],"state":{...}}
+//   {"ok":false,"error":{"code":"no_such_world","message":"there is no world 9"}}
+//
+// Moves go through Game::apply, the same checks the board's push and claim use.
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Command{
+    New{
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        preset: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        seed: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rules: Option<Rules>,
+    },
+    State,
+    Play{ slot: u8, world: u8 },
+    Claim{ slot: u8 },
+    Move{ notation: String },
+    Undo,
+}
+
+impl Command{
+    pub fn from_move(mv: Move) -> Self{
+        match mv{
+            Move::Push{ slot, world } => Command::Play{ slot, world },
+            Move::Claim{ slot } => Command::Claim{ slot },
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode{
+    BadCommand,
+    UnknownPreset,
+    BadRules,
+    NoGame,
+    GameOver,
+    NoSuchSlot,
+    NoSuchWorld,
+    BadNotation,
+    NothingToUndo,
+    BadGame, // the game reached a state the rules do not allow
+    NotAllowed, // the command is valid, but whoever runs the game has ruled it out
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ProtocolError{
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ProtocolError{
+    pub fn new(code: ErrorCode, message: impl fmt::Display) -> Self{
+        ProtocolError{ code, message: message.to_string() }
+    }
+}
+
+impl From<MoveError> for ProtocolError{
+    fn from(err: MoveError) -> Self{
+        let code = match err{
+            MoveError::BadGame => ErrorCode::BadGame,
+            MoveError::GameOver => ErrorCode::GameOver,
+            MoveError::NoSuchSlot(_) => ErrorCode::NoSuchSlot,
+            MoveError::NoSuchWorld(_) => ErrorCode::NoSuchWorld,
+        };
+        ProtocolError::new(code, err)
+    }
+}
+
+impl fmt::Display for ProtocolError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ProtocolError{}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event{
+    Dealt{ preset: Option<RulePreset>, seed: u32 },
+    Pushed{ slot: u8, world: u8, card: i8, polarity: i8, clamped: bool, notation: String },
+    Claimed{ slot: u8, card: i8, balanced: Vec<u8>, notation: String },
+    Drew{ slot: u8, card: i8 },
+    Undone{ turn: u16 },
+    GameOver{ score: u16 },
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct State{
+    pub preset: Option<RulePreset>, // None for custom rules
+    pub seed: u32,
+    pub rules: Rules,
+    pub turn: u16,
+    pub score: u16,
+    pub pleroma: bool, // the active dimension
+    pub polarity: Vec<i8>,
+    pub hand: Vec<i8>, // the active dimension's hand
+    pub hands: Vec<Vec<i8>>, // Kenoma then Pleroma
+    pub balanced: Vec<u8>,
+    pub cards_left: usize,
+    pub over: bool,
+    #[serde(default)]
+    pub mode: Mode,
+    #[serde(default)]
+    pub scores: [u16; 2], // per player in versus
+    #[serde(default)]
+    pub player: u8, // whose turn it is in versus
+}
+
+impl State{
+    pub fn new(game: &Game, preset: Option<RulePreset>, seed: u32) -> Self{
+        State{
+            preset,
+            seed,
+            rules: game.rules.clone(),
+            turn: game.turn,
+            score: game.score,
+            pleroma: game.pleroma,
+            polarity: game.polarity.to_vec(),
+            hand: game.hand().to_vec(),
+            hands: game.hands.iter().map(|hand| hand.to_vec()).collect(),
+            balanced: game.balanced_worlds(),
+            cards_left: game.deck.len(),
+            over: game.is_over(),
+            mode: game.mode,
+            scores: game.scores,
+            player: game.player,
+        }
+    }
+}
+
+// What a move did, as events. `game` is the game after the move, `pleroma` the dimension it
+// was played in.
+pub fn move_events(mv: Move, pleroma: bool, outcome: &Outcome, game: &Game) -> Vec<Event>{
+    let notation = Notation::new(pleroma, mv, outcome, game).to_string();
+    let mut events = vec![match mv{
+        Move::Push{ slot, world } => Event::Pushed{
+            slot, world, card: outcome.card, polarity: game.polarity[world as usize], clamped: outcome.clamped, notation,
+        },
+        Move::Claim{ slot } => Event::Claimed{ slot, card: outcome.card, balanced: outcome.balanced.clone(), notation },
+    }];
+    if let Some(card) = outcome.drawn{
+        events.push(Event::Drew{ slot: mv.slot(), card });
+    }
+    if game.is_over(){
+        events.push(Event::GameOver{ score: game.score });
+    }
+    events
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct Reply{
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<Event>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<State>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ProtocolError>,
+}
+
+impl Reply{
+    pub fn error(err: ProtocolError) -> Self{
+        Reply{ error: Some(err), ..Reply::default() }
+    }
+}
+
+// The game being remote controlled, with everything needed to undo it.
+#[derive(Default)]
+pub struct Remote{
+    game: Option<Game>,
+    preset: Option<RulePreset>,
+    seed: u32,
+    undo: Vec<Game>,
+}
+
+impl Remote{
+    pub fn new() -> Self{
+        Remote::default()
+    }
+
+    pub fn game(&self) -> Option<&Game>{
+        self.game.as_ref()
+    }
+
+    // Handles a line of input and returns the reply line, without its newline.
+    pub fn handle_line(&mut self, line: &str) -> String{
+        let reply = match serde_json::from_str::<Command>(line){
+            Ok(command) => self.handle(command),
+            Err(err) => Reply::error(ProtocolError::new(ErrorCode::BadCommand, err)),
+        };
+        serde_json::to_string(&reply).expect("replies always serialize")
+    }
+
+    pub fn handle(&mut self, command: Command) -> Reply{
+        let events = match self.run(command){
+            Ok(events) => events,
+            Err(err) => return Reply::error(err),
+        };
+        Reply{ ok: true, events, state: self.state(), error: None }
+    }
+
+    fn run(&mut self, command: Command) -> Result<Vec<Event>, ProtocolError>{
+        match command{
+            Command::New{ preset, seed, rules } => {
+                let preset = match preset{
+                    Some(name) => Some(RulePreset::from_name(&name)
+                        .ok_or_else(|| ProtocolError::new(ErrorCode::UnknownPreset, format!("there is no preset called {name}")))?),
+                    None if rules.is_some() => None,
+                    None => Some(RulePreset::Classic),
+                };
+                let rules = match rules{
+                    Some(_) if preset.is_some() => return Err(ProtocolError::new(ErrorCode::BadRules, "give either a preset or rules, not both")),
+                    Some(rules) if !rules.is_valid() => return Err(ProtocolError::new(ErrorCode::BadRules, "these rules cannot deal a game")),
+                    Some(rules) => rules,
+                    None => preset.unwrap_or_default().rules(),
+                };
+                self.seed = seed.unwrap_or_else(rand::random);
+                self.preset = preset;
+                self.game = Some(Game::new(rules, self.seed));
+                self.undo.clear();
+                Ok(vec![Event::Dealt{ preset, seed: self.seed }])
+            }
+            Command::State => {
+                self.game.as_ref().ok_or_else(no_game)?;
+                Ok(Vec::new())
+            }
+            Command::Play{ slot, world } => self.play(Move::Push{ slot, world }),
+            Command::Claim{ slot } => self.play(Move::Claim{ slot }),
+            Command::Move{ notation } => {
+                let game = self.game.as_ref().ok_or_else(no_game)?;
+                let mv = notation.parse::<Notation>().and_then(|parsed| parsed.move_for(game))
+                    .map_err(|err| ProtocolError::new(ErrorCode::BadNotation, format!("{notation}: {err}")))?;
+                self.play(mv)
+            }
+            Command::Undo => {
+                let previous = self.undo.pop().ok_or_else(|| ProtocolError::new(ErrorCode::NothingToUndo, "no move to take back"))?;
+                let turn = previous.turn;
+                self.game = Some(previous);
+                Ok(vec![Event::Undone{ turn }])
+            }
+        }
+    }
+
+    fn play(&mut self, mv: Move) -> Result<Vec<Event>, ProtocolError>{
+        let game = self.game.as_mut().ok_or_else(no_game)?;
+        let before = game.clone();
+        let pleroma = game.pleroma;
+        let outcome = game.apply(mv)?;
+        let events = move_events(mv, pleroma, &outcome, game);
+        self.undo.push(before);
+        Ok(events)
+    }
+
+    pub fn state(&self) -> Option<State>{
+        self.game.as_ref().map(|game| State::new(game, self.preset, self.seed))
+    }
+}
+
+fn no_game() -> ProtocolError{
+    ProtocolError::new(ErrorCode::NoGame, "no game yet, send a new command first")
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn reply(remote: &mut Remote, line: &str) -> Reply{
+        serde_json::from_str(&remote.handle_line(line)).unwrap()
+    }
+
+    #[test]
+    fn moves_can_be_sent_in_notation(){
+        let mut remote = Remote::new();
+        assert!(reply(&mut remote, r#"{"cmd":"new","seed":7}"#).ok);
+        let card = remote.game().unwrap().hand()[2];
+        let played = reply(&mut remote, &format!(r#"{{"cmd":"move","notation":"K 3:{card} -> W2"}}"#));
+        assert!(played.ok, "{:?}", played.error);
+        assert!(matches!(played.events[0], Event::Pushed{ slot: 2, world: 1, .. }));
+        let refused = reply(&mut remote, r#"{"cmd":"move","notation":"P 1→W1"}"#);
+        assert_eq!(refused.error.map(|err| err.code), Some(ErrorCode::BadNotation)); // still Kenoma's turn
+        assert_eq!(remote.game().unwrap().turn, 1);
+    }
+
+    fn error_code(remote: &mut Remote, line: &str) -> Option<ErrorCode>{
+        reply(remote, line).error.map(|err| err.code)
+    }
+
+    #[test]
+    fn undo_takes_back_one_move_at_a_time(){
+        let mut remote = Remote::new();
+        let dealt = reply(&mut remote, r#"{"cmd":"new","preset":"Quick","seed":3}"#).state;
+        let played = reply(&mut remote, r#"{"cmd":"play","slot":1,"world":2}"#);
+        assert!(played.ok && played.state != dealt);
+        let undone = reply(&mut remote, r#"{"cmd":"undo"}"#);
+        assert_eq!(undone.events, [Event::Undone{ turn: 0 }]);
+        assert_eq!(undone.state, dealt);
+        assert_eq!(error_code(&mut remote, r#"{"cmd":"undo"}"#), Some(ErrorCode::NothingToUndo));
+        assert_eq!(remote.state(), dealt);
+    }
+
+    #[test]
+    fn illegal_moves_are_refused_without_a_trace(){
+        let mut remote = Remote::new();
+        reply(&mut remote, r#"{"cmd":"new","seed":3}"#);
+        let dealt = remote.game().cloned();
+        assert_eq!(error_code(&mut remote, r#"{"cmd":"play","slot":0,"world":9}"#), Some(ErrorCode::NoSuchWorld));
+        assert_eq!(error_code(&mut remote, r#"{"cmd":"play","slot":9,"world":0}"#), Some(ErrorCode::NoSuchSlot));
+        assert_eq!(error_code(&mut remote, r#"{"cmd":"claim","slot":9}"#), Some(ErrorCode::NoSuchSlot));
+        assert_eq!(remote.game().cloned(), dealt);
+        assert_eq!(error_code(&mut remote, r#"{"cmd":"undo"}"#), Some(ErrorCode::NothingToUndo));
+    }
+
+    #[test]
+    fn games_need_good_rules_and_a_deal(){
+        let mut remote = Remote::new();
+        assert_eq!(error_code(&mut remote, r#"{"cmd":"state"}"#), Some(ErrorCode::NoGame));
+        assert_eq!(error_code(&mut remote, r#"{"cmd":"claim","slot":0}"#), Some(ErrorCode::NoGame));
+        let rules = serde_json::to_string(&RulePreset::Tight.rules()).unwrap();
+        let both = format!(r#"{{"cmd":"new","preset":"Classic","rules":{rules}}}"#);
+        assert_eq!(error_code(&mut remote, &both), Some(ErrorCode::BadRules));
+        let mut invalid = RulePreset::Tight.rules();
+        invalid.clamp = 0;
+        let invalid = format!(r#"{{"cmd":"new","rules":{}}}"#, serde_json::to_string(&invalid).unwrap());
+        assert_eq!(error_code(&mut remote, &invalid), Some(ErrorCode::BadRules));
+        assert_eq!(error_code(&mut remote, r#"{"cmd":"new","preset":"Nope"}"#), Some(ErrorCode::UnknownPreset));
+        assert!(remote.game().is_none());
+        let custom = reply(&mut remote, &format!(r#"{{"cmd":"new","rules":{rules}}}"#));
+        assert_eq!(custom.state.map(|state| (state.preset, state.rules)), Some((None, RulePreset::Tight.rules())));
+    }
+
+    #[test]
+    fn malformed_lines_are_bad_commands(){
+        let mut remote = Remote::new();
+        for line in ["", "not json", r#"{"cmd":"dance"}"#, r#"{"cmd":"play","slot":"two"}"#, r#"{"slot":1}"#]{
+            assert_eq!(error_code(&mut remote, line), Some(ErrorCode::BadCommand), "{line}");
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::rules::{Game, Outcome, Rules, HAND_SIZE, MAX_CARD, WORLDS};
+
+// Handcrafted positions with a goal, played out with the ordinary rules. A puzzle fixes the
+// starting worlds and every card in the order it comes: the first HAND_SIZE draws are the
+// starting hand and the rest are drawn one per move, so there is no luck involved and a
+// puzzle has as many moves as it has draws after the hand.
+
+// What counts as solving a puzzle. turns is a limit on the moves, otherwise the puzzle lasts
+// until the draws run out.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "goal", rename_all = "snake_case")]
+pub enum Goal{
+    Score{ worlds: u16, #[serde(default, skip_serializing_if = "Option::is_none")] turns: Option<u16> }, // claim this many worlds in all
+    Sweep{ #[serde(default, skip_serializing_if = "Option::is_none")] turns: Option<u16> }, // claim all four worlds with a single card
+}
+
+impl Goal{
+    pub fn turns(self) -> Option<u16>{
+        match self{
+            Goal::Score{ turns, .. } | Goal::Sweep{ turns } => turns,
+        }
+    }
+
+    pub fn describe(self) -> String{
+        let what = match self{
+            Goal::Score{ worlds: 1, .. } => "Claim a world".to_owned(),
+            Goal::Score{ worlds, .. } => format!("Claim {worlds} worlds"),
+            Goal::Sweep{ .. } => format!("Claim all {WORLDS} worlds at once"),
+        };
+        match self.turns(){
+            Some(1) => format!("{what} in 1 move"),
+            Some(turns) => format!("{what} in {turns} moves"),
+            None => what,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Verdict{
+    #[default]
+    Playing,
+    Solved,
+    Failed,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PuzzleError{
+    Polarity(usize), // the world starts off the board
+    Card(usize), // a draw that is not a card
+    TooFewDraws,
+    Clamp,
+    Goal,
+}
+
+impl fmt::Display for PuzzleError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            PuzzleError::Polarity(world) => write!(f, "world {} starts past the clamp", world + 1),
+            PuzzleError::Card(draw) => write!(f, "draw {} is not a card from 1 to {MAX_CARD}", draw + 1),
+            PuzzleError::TooFewDraws => write!(f, "a puzzle needs a full hand and at least one more draw"),
+            PuzzleError::Clamp => write!(f, "the clamp must be between 1 and {MAX_CARD}"),
+            PuzzleError::Goal => write!(f, "the goal can never be reached"),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError{}
+
+fn default_clamp() -> i8{
+    6
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Puzzle{
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    pub polarity: [i8; WORLDS],
+    pub draws: Vec<i8>, // the starting hand, then the deck in the order it is drawn
+    #[serde(default)]
+    pub pleroma: bool, // the dimension the first move is played in
+    #[serde(default = "default_clamp")]
+    pub clamp: i8,
+    pub goal: Goal,
+}
+
+impl Puzzle{
+    pub fn check(&self) -> Result<(), PuzzleError>{
+        if !(1..=MAX_CARD).contains(&self.clamp){
+            return Err(PuzzleError::Clamp);
+        }
+        if let Some(world) = self.polarity.iter().position(|polarity| polarity.unsigned_abs() > self.clamp as u8){
+            return Err(PuzzleError::Polarity(world));
+        }
+        if let Some(draw) = self.draws.iter().position(|card| !(1..=MAX_CARD).contains(card)){
+            return Err(PuzzleError::Card(draw));
+        }
+        if self.draws.len() <= HAND_SIZE{
+            return Err(PuzzleError::TooFewDraws);
+        }
+        let unreachable = match self.goal{
+            Goal::Score{ worlds, .. } => worlds == 0 || worlds as usize > WORLDS * self.moves(),
+            Goal::Sweep{ .. } => false,
+        };
+        if unreachable || self.goal.turns() == Some(0){
+            return Err(PuzzleError::Goal);
+        }
+        Ok(())
+    }
+
+    // The most moves the puzzle can last.
+    pub fn moves(&self) -> usize{
+        let moves = self.draws.len().saturating_sub(HAND_SIZE);
+        self.goal.turns().map_or(moves, |turns| moves.min(turns as usize))
+    }
+
+    // The starting position as a solitaire game. The deck holds only the draws, so the game
+    // ends when they run out; checked puzzles always deal.
+    pub fn game(&self) -> Game{
+        let mut hand = [0; HAND_SIZE];
+        hand.copy_from_slice(&self.draws[..HAND_SIZE]);
+        let rules = Rules{
+            deck_size: (self.draws.len() - HAND_SIZE) as u16,
+            clamp: self.clamp,
+            starting_offsets: self.polarity.to_vec(),
+            max_card: MAX_CARD,
+        };
+        let mut game = Game::from_parts(rules, self.polarity, hand, self.draws[HAND_SIZE..].iter().rev().copied().collect());
+        game.pleroma = self.pleroma;
+        game
+    }
+
+    // Where the puzzle stands after a move, given the game after it and what it did.
+    pub fn verdict(&self, game: &Game, outcome: &Outcome) -> Verdict{
+        let solved = match self.goal{
+            Goal::Score{ worlds, .. } => game.score >= worlds,
+            Goal::Sweep{ .. } => outcome.balanced.len() == WORLDS,
+        };
+        if solved{
+            Verdict::Solved
+        } else if game.is_over() || self.goal.turns().is_some_and(|turns| game.turn >= turns){
+            Verdict::Failed
+        } else {
+            Verdict::Playing
+        }
+    }
+}
+
+// A set of puzzles played in order, as found in assets/*.pack.json.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct PuzzlePack{
+    pub name: String,
+    pub puzzles: Vec<Puzzle>,
+}
+
+impl PuzzlePack{
+    pub fn check(&self) -> Result<(), (usize, PuzzleError)>{
+        self.puzzles.iter().enumerate().try_for_each(|(index, puzzle)| puzzle.check().map_err(|err| (index, err)))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use crate::{generator, rules::Move};
+    use super::*;
+
+    fn first_light() -> Puzzle{
+        Puzzle{
+            name: "First Light".to_owned(),
+            description: String::new(),
+            polarity: [3, -2, 4, 5],
+            draws: vec![3, 1, 1, 1, 2, 2],
+            pleroma: false,
+            clamp: 6,
+            goal: Goal::Score{ worlds: 1, turns: Some(2) },
+        }
+    }
+
+    #[test]
+    fn the_shipped_pack_is_valid_and_solvable(){
+        let pack: PuzzlePack = serde_json::from_str(include_str!("../assets/puzzles.pack.json")).unwrap();
+        assert_eq!(pack.check(), Ok(()));
+        for puzzle in pack.puzzles.iter(){
+            assert!(generator::rate(puzzle, 8).is_some(), "{} cannot be solved", puzzle.name);
+        }
+    }
+
+    #[test]
+    fn broken_puzzles_are_refused(){
+        let broken = |change: fn(&mut Puzzle)| {
+            let mut puzzle = first_light();
+            change(&mut puzzle);
+            puzzle.check()
+        };
+        assert_eq!(first_light().check(), Ok(()));
+        assert_eq!(broken(|puzzle| puzzle.clamp = 0), Err(PuzzleError::Clamp));
+        assert_eq!(broken(|puzzle| puzzle.clamp = MAX_CARD + 1), Err(PuzzleError::Clamp));
+        assert_eq!(broken(|puzzle| puzzle.polarity[2] = -7), Err(PuzzleError::Polarity(2)));
+        assert_eq!(broken(|puzzle| puzzle.polarity[1] = i8::MIN), Err(PuzzleError::Polarity(1)));
+        assert_eq!(broken(|puzzle| puzzle.draws[4] = 0), Err(PuzzleError::Card(4)));
+        assert_eq!(broken(|puzzle| puzzle.draws.truncate(HAND_SIZE)), Err(PuzzleError::TooFewDraws));
+        assert_eq!(broken(|puzzle| puzzle.goal = Goal::Score{ worlds: 0, turns: None }), Err(PuzzleError::Goal));
+        assert_eq!(broken(|puzzle| puzzle.goal = Goal::Score{ worlds: 9, turns: None }), Err(PuzzleError::Goal));
+        assert_eq!(broken(|puzzle| puzzle.goal = Goal::Sweep{ turns: Some(0) }), Err(PuzzleError::Goal));
+    }
+
+    #[test]
+    fn verdicts_follow_the_goal(){
+        let puzzle = first_light();
+        let play = |moves: &[Move]| {
+            let mut game = puzzle.game();
+            moves.iter().map(|mv| {
+                let outcome = game.apply(*mv).unwrap();
+                puzzle.verdict(&game, &outcome)
+            }).collect::<Vec<_>>()
+        };
+        // Kenoma takes the first world from 3 to 0 with the 3, then any card claims it.
+        assert_eq!(play(&[Move::Push{ slot: 0, world: 0 }, Move::Claim{ slot: 0 }]), [Verdict::Playing, Verdict::Solved]);
+        assert_eq!(play(&[Move::Push{ slot: 1, world: 3 }, Move::Push{ slot: 1, world: 3 }]), [Verdict::Playing, Verdict::Failed]);
+    }
+}
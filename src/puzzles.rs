@@ -0,0 +1,250 @@
+use std::collections::BTreeSet;
+use bevy::{prelude::*, asset::{AssetLoader, LoadContext, LoadState, LoadedAsset}, reflect::{TypePath, TypeUuid}, tasks::{AsyncComputeTaskPool, Task, futures_lite::future}, utils::BoxedFuture};
+use pleromakenoma::{generator::{self, Difficulty, Rating}, puzzle::{Puzzle, PuzzlePack, Verdict}};
+use crate::{menu::{spawn_menu, AppState, MenuActivated, MenuButton, MenuCursor, MenuScreen}, session::{NextGame, Session}, storage, GameSettings};
+
+// Handcrafted puzzles, see pleromakenoma::puzzle. The pack is an asset so that new levels
+// need no rebuild; which puzzles were solved is kept with the rest of the save data.
+// Fresh puzzles come from pleromakenoma::generator instead, made up when asked for.
+
+const PACK_PATH: &str = "puzzles.pack.json";
+const SOLVED_FILE: &str = "puzzles.json";
+
+#[derive(TypeUuid, TypePath)]
+#[uuid = "5d0e6c1a-3f7b-4a52-9c2e-8b41d7f0a963"]
+pub struct PackAsset(PuzzlePack);
+
+#[derive(Default)]
+pub struct PackLoader;
+
+impl AssetLoader for PackLoader{
+    fn load<'a>(&'a self, bytes: &'a [u8], load_context: &'a mut LoadContext) -> BoxedFuture<'a, Result<(), bevy::asset::Error>>{
+        Box::pin(async move {
+            let pack: PuzzlePack = serde_json::from_slice(bytes)?;
+            if let Err((index, err)) = pack.check(){ // caught here rather than on the board
+                return Err(bevy::asset::Error::msg(format!("puzzle {} of {}: {err}", index + 1, pack.name)));
+            }
+            load_context.set_default_asset(LoadedAsset::new(PackAsset(pack)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str]{
+        &["pack.json"]
+    }
+}
+
+#[derive(Resource)]
+pub struct Puzzles{
+    handle: Handle<PackAsset>,
+    pack: Option<PuzzlePack>, // once loaded
+    solved: BTreeSet<String>, // "pack/puzzle", so that packs can share puzzle names
+    playing: Option<usize>, // the puzzle last dealt from the select screen
+    shown: (LoadState, Option<usize>), // what the select screen shows: the pack loading, or the puzzle under the cursor
+    fresh: Option<Task<Option<(Puzzle, Rating)>>>, // a hard one can take seconds to find
+    status: Option<String>,
+}
+
+impl FromWorld for Puzzles{
+    fn from_world(world: &mut World) -> Self{
+        Puzzles{
+            handle: world.resource::<AssetServer>().load(PACK_PATH),
+            pack: None,
+            solved: storage::load(SOLVED_FILE).unwrap_or_default(),
+            playing: None,
+            shown: (LoadState::NotLoaded, None),
+            fresh: None,
+            status: None,
+        }
+    }
+}
+
+impl Puzzles{
+    fn key(pack: &PuzzlePack, puzzle: &Puzzle) -> String{
+        format!("{}/{}", pack.name, puzzle.name)
+    }
+
+    fn is_solved(&self, pack: &PuzzlePack, puzzle: &Puzzle) -> bool{
+        self.solved.contains(&Puzzles::key(pack, puzzle))
+    }
+
+    // The puzzle after the one on the board, if there is one to go on to.
+    pub fn next(&self) -> Option<usize>{
+        let next = self.playing? + 1;
+        (next < self.pack.as_ref()?.puzzles.len()).then_some(next)
+    }
+
+    fn deal(&mut self, index: usize, next_game: &mut NextGame, next_state: &mut NextState<AppState>){
+        let Some(puzzle) = self.pack.as_ref().and_then(|pack| pack.puzzles.get(index)) else { return };
+        next_game.puzzle = Some(puzzle.clone());
+        next_game.moves.clear();
+        self.playing = Some(index);
+        next_state.set(AppState::Dealing);
+    }
+}
+
+pub fn receive_pack(mut puzzles: ResMut<Puzzles>, packs: Res<Assets<PackAsset>>){
+    if puzzles.pack.is_none(){
+        puzzles.pack = packs.get(&puzzles.handle).map(|asset| asset.0.clone());
+    }
+}
+
+fn puzzles_title(puzzles: &Puzzles) -> String{
+    let Some(pack) = &puzzles.pack else { return "Puzzles".to_owned() };
+    let solved = pack.puzzles.iter().filter(|puzzle| puzzles.is_solved(pack, puzzle)).count();
+    format!("{} {solved}/{}", pack.name, pack.puzzles.len())
+}
+
+// A single line, as the whole pack has to fit on the screen below it.
+fn puzzles_lines(puzzles: &Puzzles, load_state: LoadState, cursor: usize) -> Vec<String>{
+    let line = match (puzzles.pack.as_ref().and_then(|pack| pack.puzzles.get(cursor)), &puzzles.status){
+        (Some(puzzle), _) if puzzle.description.is_empty() => puzzle.goal.describe(),
+        (Some(puzzle), _) => format!("{}. {}", puzzle.goal.describe(), puzzle.description),
+        (None, Some(status)) => status.clone(),
+        (None, None) if puzzles.pack.is_some() => "A new puzzle with a single solution, made up on the spot".to_owned(),
+        (None, None) if load_state == LoadState::Failed => format!("Cannot load assets/{PACK_PATH}, see the log"),
+        (None, None) => "Loading...".to_owned(),
+    };
+    vec![line, String::new()]
+}
+
+fn puzzles_items(puzzles: &Puzzles, difficulty: Difficulty) -> Vec<(MenuButton, String, bool)>{
+    let mut items: Vec<_> = puzzles.pack.iter().flat_map(|pack| pack.puzzles.iter().enumerate().map(move |(index, puzzle)| {
+        let mark = if puzzles.is_solved(pack, puzzle) { "[x]" } else { "[ ]" };
+        (MenuButton::Puzzle(index), format!("{mark} {}. {}", index + 1, puzzle.name), true)
+    })).collect();
+    items.push((MenuButton::FreshPuzzle, format!("Fresh Puzzle: {}", difficulty.name()), puzzles.fresh.is_none()));
+    items.push((MenuButton::Back, "Back".to_owned(), true));
+    items
+}
+
+pub fn spawn_puzzles(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<GameSettings>,
+    mut puzzles: ResMut<Puzzles>,
+    mut cursor: ResMut<MenuCursor>,
+){
+    // Back on the select screen after a puzzle, the cursor stays on it.
+    cursor.index = puzzles.playing.unwrap_or(0);
+    puzzles.fresh = None; // dropping the task cancels it
+    puzzles.status = None;
+    let load_state = asset_server.get_load_state(&puzzles.handle);
+    puzzles.shown = (load_state, puzzles.pack.is_some().then_some(cursor.index));
+    let lines = puzzles_lines(&puzzles, load_state, cursor.index);
+    spawn_menu(&mut commands, &asset_server, &puzzles_title(&puzzles), lines, puzzles_items(&puzzles, settings.puzzle_difficulty), Color::BLACK);
+}
+
+// Redraws the select screen when the pack arrives or the cursor moves to another puzzle, and
+// deals a fresh puzzle once it is found.
+pub fn step_puzzles(
+    mut commands: Commands,
+    mut events: EventReader<MenuActivated>,
+    asset_server: Res<AssetServer>,
+    settings: Res<GameSettings>,
+    cursor: Res<MenuCursor>,
+    mut puzzles: ResMut<Puzzles>,
+    mut next_game: ResMut<NextGame>,
+    mut next_state: ResMut<NextState<AppState>>,
+    query_screen: Query<Entity, With<MenuScreen>>,
+){
+    let mut changed = false;
+    for MenuActivated(button) in events.iter(){
+        match button{
+            MenuButton::Puzzle(index) => {
+                puzzles.deal(*index, &mut next_game, &mut next_state);
+                return;
+            }
+            MenuButton::FreshPuzzle => {
+                let (difficulty, seed) = (settings.puzzle_difficulty, rand::random());
+                puzzles.fresh = Some(AsyncComputeTaskPool::get().spawn(async move { generator::generate(difficulty, seed) }));
+                puzzles.status = Some(format!("Looking for a {} puzzle...", difficulty.name().to_lowercase()));
+            }
+            _ => continue,
+        }
+        changed = true;
+    }
+    if let Some(task) = puzzles.fresh.as_mut(){
+        if let Some(found) = future::block_on(future::poll_once(task)){
+            puzzles.fresh = None;
+            match found{
+                Some((puzzle, _)) => {
+                    next_game.puzzle = Some(puzzle);
+                    next_game.moves.clear();
+                    puzzles.playing = None; // not from the pack, so there is no next one
+                    next_state.set(AppState::Dealing);
+                    return;
+                }
+                None => puzzles.status = Some("No puzzle turned up this time, try again".to_owned()),
+            }
+            changed = true;
+        }
+    }
+    let load_state = asset_server.get_load_state(&puzzles.handle);
+    let shown = (load_state, puzzles.pack.is_some().then_some(cursor.index));
+    if shown == puzzles.shown && !changed{
+        return;
+    }
+    puzzles.shown = shown;
+    for entity in query_screen.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+    let lines = puzzles_lines(&puzzles, load_state, cursor.index);
+    spawn_menu(&mut commands, &asset_server, &puzzles_title(&puzzles), lines, puzzles_items(&puzzles, settings.puzzle_difficulty), Color::BLACK);
+}
+
+// Retry and Next Puzzle on the game over screen.
+pub fn continue_puzzles(
+    mut events: EventReader<MenuActivated>,
+    session: Res<Session>,
+    mut puzzles: ResMut<Puzzles>,
+    mut next_game: ResMut<NextGame>,
+    mut next_state: ResMut<NextState<AppState>>,
+){
+    for MenuActivated(button) in events.iter(){
+        match button{
+            MenuButton::Retry => {
+                next_game.puzzle = session.puzzle.clone();
+                next_state.set(AppState::Dealing);
+            }
+            MenuButton::NextPuzzle => {
+                if let Some(next) = puzzles.next(){
+                    puzzles.deal(next, &mut next_game, &mut next_state);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+pub fn record_solved(session: Res<Session>, mut puzzles: ResMut<Puzzles>){
+    if session.verdict != Verdict::Solved{
+        return;
+    }
+    let (Some(pack), Some(puzzle)) = (&puzzles.pack, &session.puzzle) else { return };
+    if !pack.puzzles.contains(puzzle){ // a fresh one
+        return;
+    }
+    let key = Puzzles::key(pack, puzzle);
+    if puzzles.solved.insert(key){
+        storage::save(SOLVED_FILE, &puzzles.solved);
+    }
+}
+
+pub fn game_over_lines(session: &Session) -> Vec<String>{
+    let Some(puzzle) = &session.puzzle else { return Vec::new() };
+    let verdict = match session.verdict{
+        Verdict::Solved => format!("Solved in {} moves", session.game.turn),
+        Verdict::Failed | Verdict::Playing => "Not solved".to_owned(),
+    };
+    vec![puzzle.name.clone(), puzzle.goal.describe(), verdict, String::new()]
+}
+
+pub fn game_over_items(puzzles: &Puzzles) -> Vec<(MenuButton, String, bool)>{
+    vec![
+        (MenuButton::Retry, "Retry".to_owned(), true),
+        (MenuButton::NextPuzzle, "Next Puzzle".to_owned(), puzzles.next().is_some()),
+        (MenuButton::Puzzles, "Puzzles".to_owned(), true),
+        (MenuButton::MainMenu, "Main Menu".to_owned(), true),
+    ]
+}
@@ -0,0 +1,473 @@
+use std::fmt;
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+pub const WORLDS: usize = 4;
+pub const HAND_SIZE: usize = 4;
+pub const MOVE_COUNT: usize = HAND_SIZE * (WORLDS + 1); // every push and claim, legal or not
+pub const MAX_CARD: i8 = 6; // the highest card the board has a face for, and the widest clamp
+
+// Knobs for tuning the game. Polarities past 6 would fall off the board, so clamp stays within it.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Rules{
+    pub deck_size: u16,
+    pub clamp: i8,
+    pub starting_offsets: Vec<i8>, // each world starts on one of these, picked at random
+    pub max_card: i8, // cards are drawn evenly from 1..=max_card
+}
+
+impl Rules{
+    // Whether a game can be dealt with these, for rules that come from outside the game.
+    pub fn is_valid(&self) -> bool{
+        (1..=MAX_CARD).contains(&self.clamp) && (1..=MAX_CARD).contains(&self.max_card) && !self.starting_offsets.is_empty()
+            && self.starting_offsets.iter().all(|offset| offset.unsigned_abs() <= self.clamp as u8)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+pub enum RulePreset{
+    #[default]
+    Classic,
+    Quick,
+    Marathon,
+    Tight,
+}
+
+impl RulePreset{
+    pub const ALL: [RulePreset; 4] = [RulePreset::Classic, RulePreset::Quick, RulePreset::Marathon, RulePreset::Tight];
+
+    pub fn rules(self) -> Rules{
+        let classic = Rules{
+            deck_size: 21,
+            clamp: 6,
+            starting_offsets: vec![-4, -3, -2, 2, 3, 4],
+            max_card: 6,
+        };
+        match self{
+            RulePreset::Classic => classic,
+            RulePreset::Quick => Rules{ deck_size: 12, ..classic },
+            RulePreset::Marathon => Rules{ deck_size: 42, ..classic },
+            RulePreset::Tight => Rules{ clamp: 4, starting_offsets: vec![-3, -2, 2, 3], ..classic },
+        }
+    }
+
+    pub fn name(self) -> &'static str{
+        match self{
+            RulePreset::Classic => "Classic",
+            RulePreset::Quick => "Quick",
+            RulePreset::Marathon => "Marathon",
+            RulePreset::Tight => "Tight",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self>{
+        RulePreset::ALL.into_iter().find(|preset| preset.name().eq_ignore_ascii_case(name))
+    }
+}
+
+// Who is playing. In Hotseat two players take turns on the same board and hands, and each
+// claim is credited to whoever made it. In Dimensions one player owns Kenoma and the other
+// Pleroma, each with a hand of their own, and a dimension's turn lasts until it claims.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+pub enum Mode{
+    #[default]
+    Solitaire,
+    Hotseat,
+    Dimensions,
+}
+
+impl Mode{
+    pub const ALL: [Mode; 3] = [Mode::Solitaire, Mode::Hotseat, Mode::Dimensions];
+
+    pub fn name(self) -> &'static str{
+        match self{
+            Mode::Solitaire => "Solitaire",
+            Mode::Hotseat => "Hotseat",
+            Mode::Dimensions => "Kenoma vs Pleroma",
+        }
+    }
+
+    pub fn is_versus(self) -> bool{
+        self != Mode::Solitaire
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Move{
+    Push{ slot: u8, world: u8 }, // play a card onto a world
+    Claim{ slot: u8 }, // score every balanced world, the card becomes their new polarity, up to the clamp
+}
+
+impl Move{
+    pub fn slot(self) -> u8{
+        match self{
+            Move::Push{ slot, .. } | Move::Claim{ slot } => slot,
+        }
+    }
+
+    // A number below MOVE_COUNT for each move, in the order legal_moves lists them.
+    pub fn index(self) -> usize{
+        match self{
+            Move::Push{ slot, world } => slot as usize * (WORLDS + 1) + world as usize,
+            Move::Claim{ slot } => slot as usize * (WORLDS + 1) + WORLDS,
+        }
+    }
+
+    pub fn from_index(index: usize) -> Option<Self>{
+        if index >= MOVE_COUNT{
+            return None;
+        }
+        let slot = (index / (WORLDS + 1)) as u8;
+        Some(match index % (WORLDS + 1){
+            WORLDS => Move::Claim{ slot },
+            world => Move::Push{ slot, world: world as u8 },
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MoveError{
+    BadGame, // a game no deal leads to, such as one deserialized from elsewhere with a third player
+    GameOver,
+    NoSuchSlot(u8),
+    NoSuchWorld(u8),
+}
+
+impl fmt::Display for MoveError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            MoveError::BadGame => write!(f, "the game is in a state the rules never reach"),
+            MoveError::GameOver => write!(f, "the game is over"),
+            MoveError::NoSuchSlot(slot) => write!(f, "there is no hand slot {slot}"),
+            MoveError::NoSuchWorld(world) => write!(f, "there is no world {world}"),
+        }
+    }
+}
+
+impl std::error::Error for MoveError{}
+
+// What a move did, for the presentation and anything keeping statistics.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Outcome{
+    pub card: i8,
+    pub balanced: Vec<u8>, // worlds scored by a claim
+    pub clamped: bool, // a push went past the clamp and was cut short
+    pub drawn: Option<i8>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Game{
+    pub rules: Rules,
+    pub polarity: [i8; WORLDS],
+    pub hands: [[i8; HAND_SIZE]; 2], // Kenoma then Pleroma; the same cards unless the mode is Dimensions
+    pub deck: Vec<i8>, // cards still to be drawn, the next one last
+    pub pleroma: bool, // the active dimension
+    pub score: u16,
+    pub turn: u16,
+    #[serde(default)]
+    pub mode: Mode,
+    #[serde(default)]
+    pub scores: [u16; 2], // worlds claimed by each player in versus, their sum is score
+    #[serde(default)]
+    pub player: u8, // whose turn it is in versus, always 0 in solitaire and the dimension in Dimensions
+}
+
+impl Game{
+    pub fn new(rules: Rules, seed: u32) -> Self{
+        Game::deal(rules, seed, Mode::Solitaire)
+    }
+
+    // The same seed deals the same worlds, hand and deck in every mode. Dimensions draws
+    // Pleroma's own hand after all of those.
+    pub fn deal(rules: Rules, seed: u32, mode: Mode) -> Self{
+        let mut rng = ChaCha8Rng::seed_from_u64(seed as u64);
+        let mut polarity = [0; WORLDS];
+        for world in polarity.iter_mut(){
+            *world = *rules.starting_offsets.choose(&mut rng).expect("rules need at least one starting offset");
+        }
+        let mut hand = [0; HAND_SIZE];
+        for card in hand.iter_mut(){
+            *card = rng.gen_range(1..=rules.max_card);
+        }
+        let deck = (0..rules.deck_size).map(|_| rng.gen_range(1..=rules.max_card)).collect();
+        let mut game = Game::from_parts(rules, polarity, hand, deck);
+        game.mode = mode;
+        if mode == Mode::Dimensions{
+            for card in game.hands[1].iter_mut(){
+                *card = rng.gen_range(1..=game.rules.max_card);
+            }
+        }
+        game
+    }
+
+    pub fn from_parts(rules: Rules, polarity: [i8; WORLDS], hand: [i8; HAND_SIZE], deck: Vec<i8>) -> Self{
+        Game{
+            rules,
+            polarity,
+            hands: [hand, hand],
+            deck,
+            pleroma: false,
+            score: 0,
+            turn: 0,
+            mode: Mode::Solitaire,
+            scores: [0; 2],
+            player: 0,
+        }
+    }
+
+    pub fn hand(&self) -> &[i8; HAND_SIZE]{
+        &self.hands[self.pleroma as usize]
+    }
+
+    pub fn is_over(&self) -> bool{
+        self.deck.is_empty()
+    }
+
+    pub fn balanced_worlds(&self) -> Vec<u8>{
+        (0..WORLDS as u8).filter(|world| self.polarity[*world as usize] == 0).collect()
+    }
+
+    pub fn legal_moves(&self) -> Vec<Move>{
+        if self.is_over(){
+            return Vec::new();
+        }
+        let mut moves = Vec::with_capacity(MOVE_COUNT);
+        for slot in 0..HAND_SIZE as u8{
+            for world in 0..WORLDS as u8{
+                moves.push(Move::Push{ slot, world });
+            }
+            moves.push(Move::Claim{ slot });
+        }
+        moves
+    }
+
+    // The legal moves minus those that would lead to the same position, as worlds on the
+    // same polarity and cards of the same value are interchangeable.
+    pub fn distinct_moves(&self) -> Vec<Move>{
+        let hand = self.hand();
+        self.legal_moves().into_iter().filter(|mv| {
+            let slot = mv.slot() as usize;
+            let first_slot = !hand[..slot].contains(&hand[slot]);
+            let first_world = match mv{
+                Move::Push{ world, .. } => !self.polarity[..*world as usize].contains(&self.polarity[*world as usize]),
+                Move::Claim{ .. } => true,
+            };
+            first_slot && first_world
+        }).collect()
+    }
+
+    pub fn check(&self, mv: Move) -> Result<(), MoveError>{
+        if self.player > 1 || self.rules.clamp < 1{ // either would panic in apply, a wide clamp only saturates
+            return Err(MoveError::BadGame);
+        }
+        if self.is_over(){
+            return Err(MoveError::GameOver);
+        }
+        if mv.slot() as usize >= HAND_SIZE{
+            return Err(MoveError::NoSuchSlot(mv.slot()));
+        }
+        if let Move::Push{ world, .. } = mv{
+            if world as usize >= WORLDS{
+                return Err(MoveError::NoSuchWorld(world));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn apply(&mut self, mv: Move) -> Result<Outcome, MoveError>{
+        self.check(mv)?;
+        let slot = mv.slot() as usize;
+        let dimension = self.pleroma as usize;
+        let card = self.hand()[slot];
+        let mut outcome = Outcome{ card, ..Outcome::default() };
+        match mv{
+            Move::Push{ world, .. } => { // Kenoma pulls polarity down, Pleroma pushes it up
+                let pol = &mut self.polarity[world as usize];
+                // Saturating, as a game deserialized from elsewhere may hold any polarity or card.
+                let pushed = if self.pleroma { pol.saturating_add(card) } else { pol.saturating_sub(card) };
+                *pol = pushed.clamp(-self.rules.clamp, self.rules.clamp);
+                outcome.clamped = *pol != pushed;
+            }
+            Move::Claim{ .. } => {
+                outcome.balanced = self.balanced_worlds();
+                let polarity = if self.pleroma { card } else { -card };
+                for world in outcome.balanced.iter(){
+                    self.polarity[*world as usize] = polarity.clamp(-self.rules.clamp, self.rules.clamp);
+                }
+                self.score += outcome.balanced.len() as u16;
+                self.scores[self.player as usize] += outcome.balanced.len() as u16;
+                self.pleroma = !self.pleroma;
+            }
+        }
+        outcome.drawn = self.deck.pop();
+        if let Some(drawn) = outcome.drawn{
+            for (owner, hand) in self.hands.iter_mut().enumerate(){
+                if self.mode != Mode::Dimensions || owner == dimension{
+                    hand[slot] = drawn;
+                }
+            }
+        }
+        self.turn += 1;
+        self.player = match self.mode{
+            Mode::Solitaire => 0,
+            Mode::Hotseat => 1 - self.player,
+            Mode::Dimensions => self.pleroma as u8,
+        };
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn classic(polarity: [i8; WORLDS], hand: [i8; HAND_SIZE], deck: Vec<i8>) -> Game{
+        Game::from_parts(RulePreset::Classic.rules(), polarity, hand, deck)
+    }
+
+    #[test]
+    fn deal_follows_the_seed_and_the_rules(){
+        let rules = RulePreset::Tight.rules();
+        let game = Game::new(rules.clone(), 7);
+        assert_eq!(game, Game::new(rules.clone(), 7));
+        assert_ne!(game, Game::new(rules.clone(), 8));
+        assert!(game.polarity.iter().all(|pol| rules.starting_offsets.contains(pol)));
+        assert_eq!(game.deck.len(), rules.deck_size as usize);
+        assert!(game.hand().iter().chain(game.deck.iter()).all(|card| (1..=rules.max_card).contains(card)));
+    }
+
+    #[test]
+    fn every_mode_deals_the_same_board(){
+        let solitaire = Game::new(RulePreset::Classic.rules(), 42);
+        for mode in Mode::ALL{
+            let game = Game::deal(RulePreset::Classic.rules(), 42, mode);
+            assert_eq!((game.polarity, game.hands[0], &game.deck), (solitaire.polarity, solitaire.hands[0], &solitaire.deck));
+            if mode != Mode::Dimensions{
+                assert_eq!(game.hands[1], game.hands[0]);
+            }
+        }
+    }
+
+    #[test]
+    fn pushes_pull_in_kenoma_and_push_in_pleroma(){
+        let mut game = classic([2, 2, 5, -5], [3, 4, 1, 1], vec![6, 6, 6, 6]);
+        let outcome = game.apply(Move::Push{ slot: 0, world: 0 }).unwrap();
+        assert_eq!((game.polarity[0], outcome.card, outcome.clamped, outcome.drawn), (-1, 3, false, Some(6)));
+        assert_eq!(game.hand()[0], 6);
+        game.apply(Move::Push{ slot: 1, world: 3 }).unwrap();
+        assert_eq!(game.polarity[3], -6, "cut short at the clamp");
+        game.pleroma = true;
+        let outcome = game.apply(Move::Push{ slot: 0, world: 2 }).unwrap();
+        assert_eq!((game.polarity[2], outcome.clamped), (6, true));
+        assert_eq!((game.turn, game.score, game.pleroma), (3, 0, true));
+    }
+
+    #[test]
+    fn claims_score_every_balanced_world_and_cross_over(){
+        let mut game = classic([0, 2, 0, -3], [3, 4, 1, 1], vec![5]);
+        let outcome = game.apply(Move::Claim{ slot: 0 }).unwrap();
+        assert_eq!(outcome.balanced, vec![0, 2]);
+        assert_eq!(game.polarity, [-3, 2, -3, -3]);
+        assert_eq!((game.score, game.pleroma, game.hand()[0]), (2, true, 5));
+        assert!(game.is_over());
+        assert_eq!(game.apply(Move::Claim{ slot: 0 }), Err(MoveError::GameOver));
+    }
+
+    #[test]
+    fn bad_moves_are_refused_without_a_change(){
+        let mut game = classic([2, 2, 2, 2], [1, 1, 1, 1], vec![1]);
+        let before = game.clone();
+        assert_eq!(game.apply(Move::Push{ slot: 4, world: 0 }), Err(MoveError::NoSuchSlot(4)));
+        assert_eq!(game.apply(Move::Push{ slot: 0, world: 4 }), Err(MoveError::NoSuchWorld(4)));
+        assert_eq!(game, before);
+    }
+
+    #[test]
+    fn hotseat_credits_claims_to_whoever_made_them(){
+        let mut game = classic([1, 0, 3, 3], [1, 2, 2, 2], vec![2, 2, 2]);
+        game.mode = Mode::Hotseat;
+        game.apply(Move::Push{ slot: 0, world: 0 }).unwrap();
+        assert_eq!(game.player, 1);
+        game.apply(Move::Claim{ slot: 1 }).unwrap();
+        assert_eq!((game.scores, game.score, game.player), ([0, 2], 2, 0));
+    }
+
+    #[test]
+    fn dimensions_draw_into_the_movers_hand_only(){
+        let mut game = classic([0, 3, 3, 3], [1, 2, 3, 4], vec![6, 5]);
+        game.mode = Mode::Dimensions;
+        game.hands[1] = [4, 4, 4, 4];
+        game.apply(Move::Push{ slot: 1, world: 1 }).unwrap();
+        assert_eq!((game.hands, game.player), ([[1, 5, 3, 4], [4, 4, 4, 4]], 0));
+        game.apply(Move::Claim{ slot: 0 }).unwrap();
+        assert_eq!((game.hands, game.player, game.scores), ([[6, 5, 3, 4], [4, 4, 4, 4]], 1, [1, 0]));
+    }
+
+    #[test]
+    fn rules_from_outside_stay_on_the_board(){
+        let classic = RulePreset::Classic.rules();
+        assert!(RulePreset::ALL.iter().all(|preset| preset.rules().is_valid()));
+        assert!(Rules{ clamp: 1, max_card: 1, starting_offsets: vec![-1, 1], ..classic.clone() }.is_valid());
+        assert!(Rules{ clamp: MAX_CARD, max_card: MAX_CARD, ..classic.clone() }.is_valid());
+        for rules in [
+            Rules{ clamp: 0, starting_offsets: vec![0], ..classic.clone() },
+            Rules{ clamp: MAX_CARD + 1, ..classic.clone() },
+            Rules{ max_card: 0, ..classic.clone() },
+            Rules{ max_card: MAX_CARD + 1, ..classic.clone() },
+            Rules{ max_card: i8::MAX, ..classic.clone() },
+            Rules{ starting_offsets: Vec::new(), ..classic.clone() },
+            Rules{ starting_offsets: vec![2, -7], ..classic.clone() },
+        ]{
+            assert!(!rules.is_valid(), "{rules:?}");
+        }
+    }
+
+    #[test]
+    fn nothing_lands_past_the_clamp(){
+        let tight = RulePreset::Tight.rules();
+        let mut game = Game::from_parts(tight, [0, -4, 4, 3], [MAX_CARD; HAND_SIZE], vec![MAX_CARD; 3]);
+        let outcome = game.apply(Move::Claim{ slot: 0 }).unwrap();
+        assert_eq!((outcome.balanced, game.polarity[0]), (vec![0], -4));
+        let outcome = game.apply(Move::Push{ slot: 1, world: 1 }).unwrap(); // Pleroma now
+        assert_eq!((game.polarity[1], outcome.clamped), (2, false));
+        game.apply(Move::Push{ slot: 2, world: 2 }).unwrap();
+        assert_eq!(game.polarity[2], 4);
+    }
+
+    #[test]
+    fn extreme_values_saturate_instead_of_overflowing(){
+        let rules = Rules{ clamp: i8::MAX, max_card: i8::MAX, ..RulePreset::Classic.rules() }; // not valid, but games come from anywhere
+        let mut game = Game::from_parts(rules, [i8::MAX, i8::MIN, 0, 0], [i8::MAX; HAND_SIZE], vec![1; 3]);
+        game.pleroma = true;
+        game.apply(Move::Push{ slot: 0, world: 0 }).unwrap();
+        game.pleroma = false;
+        game.apply(Move::Push{ slot: 0, world: 1 }).unwrap();
+        assert_eq!(&game.polarity[..2], &[i8::MAX, -i8::MAX]);
+        game.apply(Move::Claim{ slot: 1 }).unwrap();
+        assert_eq!(&game.polarity[2..], &[-i8::MAX, -i8::MAX]);
+    }
+
+    #[test]
+    fn broken_games_are_refused_instead_of_panicking(){
+        let mut game: Game = serde_json::from_str(&serde_json::to_string(&Game::deal(RulePreset::Classic.rules(), 1, Mode::Hotseat)).unwrap()).unwrap();
+        game.player = 5;
+        let before = game.clone();
+        assert_eq!(game.apply(Move::Claim{ slot: 0 }), Err(MoveError::BadGame));
+        assert_eq!(game, before);
+        for clamp in [0, -3, i8::MIN]{
+            let mut game = Game::new(Rules{ clamp, ..RulePreset::Classic.rules() }, 1);
+            assert_eq!(game.apply(Move::Push{ slot: 0, world: 0 }), Err(MoveError::BadGame));
+            assert_eq!(game.apply(Move::Claim{ slot: 0 }), Err(MoveError::BadGame));
+        }
+    }
+
+    #[test]
+    fn move_indices_match_legal_moves(){
+        let game = classic([2, 2, 2, 2], [1, 2, 3, 4], vec![1]);
+        for (index, mv) in game.legal_moves().into_iter().enumerate(){
+            assert_eq!((mv.index(), Move::from_index(index)), (index, Some(mv)));
+        }
+        assert_eq!(Move::from_index(MOVE_COUNT), None);
+    }
+}
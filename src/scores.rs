@@ -0,0 +1,46 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use pleromakenoma::rules::RulePreset;
+use crate::storage;
+
+const SCORES_FILE: &str = "scores.json";
+const MAX_SCORES: usize = 100;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScoreRecord{
+    pub score: u16,
+    pub seed: u32,
+    pub preset: RulePreset,
+    pub date: u64, // unix seconds
+    pub duration: f32, // seconds spent playing, pauses excluded
+    pub turns: u16,
+}
+
+// The best finished games, highest score first. Ties go to the faster game, then the older one.
+#[derive(Resource, Default, Serialize, Deserialize)]
+pub struct Leaderboard{
+    pub records: Vec<ScoreRecord>,
+}
+
+impl Leaderboard{
+    pub fn load() -> Self{
+        storage::load(SCORES_FILE).unwrap_or_default()
+    }
+
+    pub fn save(&self){
+        storage::save(SCORES_FILE, self);
+    }
+
+    pub fn add(&mut self, record: ScoreRecord){
+        self.records.push(record);
+        self.records.sort_by(|a, b| b.score.cmp(&a.score)
+            .then(a.duration.total_cmp(&b.duration))
+            .then(a.date.cmp(&b.date)));
+        self.records.truncate(MAX_SCORES);
+        self.save();
+    }
+
+    pub fn filtered(&self, filter: Option<RulePreset>) -> impl Iterator<Item = &ScoreRecord>{
+        self.records.iter().filter(move |record| filter.is_none_or(|preset| record.preset == preset))
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::VecDeque;
+use bevy::prelude::*;
+use pleromakenoma::{code::GameCode, notation::Notation, puzzle::{Puzzle, Verdict}, rules::{Game, Mode, Move, Outcome, RulePreset}};
+use crate::{daily::{self, DailyRecord}, date, input::{InputAction, InputQueue}, menu::AppState, scores::{Leaderboard, ScoreRecord}, stats::LifetimeStats, GameSettings, WORLD_PHASE};
+
+const GAME_OVER_DELAY: f32 = 1.5; // seconds to look at the final board before the scores come up
+
+#[derive(Clone)]
+pub struct Turn{
+    pub mv: Move,
+    pub pleroma: bool, // the dimension the move was played in
+    pub outcome: Outcome,
+    pub notation: Notation,
+}
+
+// The game being played. The board entities only animate what the rules already decided.
+#[derive(Resource)]
+pub struct Session{
+    pub game: Game,
+    pub seed: u32,
+    pub preset: RulePreset,
+    pub history: Vec<Turn>,
+    pub hint: Option<Notation>, // the move suggested for the current turn
+    pub daily: Option<u64>, // the day whose challenge this is
+    pub puzzle: Option<Puzzle>, // the puzzle being played, which replaces the deal
+    pub verdict: Verdict,
+    pub replay: VecDeque<Move>, // imported moves still to be played out on the board
+    pub scored: bool, // false once moves were imported, as those were not played here
+    pub elapsed: f32, // seconds spent in AppState::Playing
+    pub over_for: f32, // seconds since the last card was drawn
+    pub recorded: bool, // the finished game is already on the leaderboard
+}
+
+impl Session{
+    pub fn new(preset: RulePreset, seed: u32) -> Self{
+        Session{
+            game: Game::new(preset.rules(), seed),
+            seed,
+            preset,
+            history: Vec::new(),
+            hint: None,
+            daily: None,
+            puzzle: None,
+            verdict: Verdict::Playing,
+            replay: VecDeque::new(),
+            scored: true,
+            elapsed: 0.,
+            over_for: 0.,
+            recorded: false,
+        }
+    }
+
+    pub fn play(&mut self, mv: Move) -> Outcome{
+        let pleroma = self.game.pleroma;
+        let outcome = self.game.apply(mv).expect("the board only offers legal moves");
+        let notation = Notation::new(pleroma, mv, &outcome, &self.game);
+        self.hint = None;
+        if let Some(puzzle) = &self.puzzle{
+            self.verdict = puzzle.verdict(&self.game, &outcome);
+            if self.verdict != Verdict::Playing{ // the rest of the draws go unused, ending the game here
+                self.game.deck.clear();
+            }
+        }
+        self.history.push(Turn{ mv, pleroma, outcome: outcome.clone(), notation });
+        outcome
+    }
+
+    pub fn code(&self) -> GameCode{
+        GameCode{
+            preset: self.preset,
+            mode: self.game.mode,
+            seed: self.seed,
+            moves: self.history.iter().map(|turn| turn.mv).collect(),
+        }
+    }
+}
+
+impl Default for Session{
+    fn default() -> Self{
+        Session::new(RulePreset::default(), 0)
+    }
+}
+
+// What the next deal will be. The menus fill this in before entering AppState::Dealing.
+#[derive(Resource, Default)]
+pub struct NextGame{
+    pub preset: RulePreset,
+    pub mode: Mode,
+    pub seed: Option<u32>, // a fresh random seed when None
+    pub daily: bool, // today's challenge, a solitaire game which overrides all of the above
+    pub moves: Vec<Move>, // replayed on the board after the deal, already checked against it
+    pub puzzle: Option<Puzzle>, // overrides everything but moves, see puzzles.rs
+}
+
+pub fn new_session(mut next: ResMut<NextGame>, mut session: ResMut<Session>, mut daily_record: ResMut<DailyRecord>){
+    if let Some(puzzle) = next.puzzle.take(){
+        next.daily = false;
+        *session = Session::new(RulePreset::Classic, 0);
+        session.game = puzzle.game();
+        session.puzzle = Some(puzzle);
+        session.scored = false; // puzzles keep their own record of what was solved
+        return;
+    }
+    if next.daily{
+        next.daily = false;
+        let day = daily::today();
+        daily_record.start(day);
+        *session = Session::new(RulePreset::Classic, daily::daily_seed(day));
+        session.daily = Some(day);
+        return;
+    }
+    let seed = next.seed.take().unwrap_or_else(rand::random);
+    *session = Session::new(next.preset, seed);
+    session.replay = next.moves.drain(..).collect();
+    session.scored = session.replay.is_empty();
+    session.game = Game::deal(next.preset.rules(), seed, next.mode);
+}
+
+// Feeds imported moves through the input queue, so they animate like any other move.
+pub fn play_replay(settings: Res<GameSettings>, mut session: ResMut<Session>, mut queue: ResMut<InputQueue>){
+    let phase = unsafe { WORLD_PHASE };
+    let Some(mv) = session.replay.front().copied() else { return };
+    match (phase, mv){
+        (0, _) => queue.push(&settings, vec![InputAction::SelectSlot(mv.slot())]),
+        (3, Move::Push{ world, .. }) => {
+            queue.push(&settings, vec![InputAction::TargetWorld(world)]);
+            session.replay.pop_front();
+        }
+        (3, Move::Claim{ .. }) => {
+            queue.push(&settings, vec![InputAction::Claim]);
+            session.replay.pop_front();
+        }
+        _ => (),
+    }
+}
+
+pub fn finish_game(
+    time: Res<Time>,
+    settings: Res<GameSettings>,
+    mut session: ResMut<Session>,
+    mut leaderboard: ResMut<Leaderboard>,
+    mut stats: ResMut<LifetimeStats>,
+    mut daily_record: ResMut<DailyRecord>,
+    mut next_state: ResMut<NextState<AppState>>,
+){
+    if session.recorded{
+        return;
+    }
+    if unsafe { WORLD_PHASE } != -99{
+        session.elapsed += time.delta_seconds();
+        return;
+    }
+    session.over_for += time.delta_seconds();
+    let delay = if settings.instant_animations { 0. } else { GAME_OVER_DELAY / settings.animation_speed };
+    if session.over_for < delay{
+        return;
+    }
+    session.recorded = true;
+    if session.puzzle.is_none(){ // a puzzle is not dealt from a seed, so it has no code
+        info!("game code: {}", session.code().encode());
+    }
+    next_state.set(AppState::GameOver);
+    if !session.scored || session.game.mode.is_versus(){ // the records are for solitaire scores
+        return;
+    }
+    leaderboard.add(ScoreRecord{
+        score: session.game.score,
+        seed: session.seed,
+        preset: session.preset,
+        date: date::now(),
+        duration: session.elapsed,
+        turns: session.game.turn,
+    });
+    stats.record(&session.history, session.game.score);
+    if let Some(day) = session.daily{
+        daily_record.finish(day, session.game.score);
+    }
+}
@@ -0,0 +1,292 @@
+use std::{collections::HashMap, hash::{BuildHasherDefault, Hasher}, sync::atomic::{AtomicBool, Ordering}};
+use crate::rules::{Game, Move, HAND_SIZE, WORLDS};
+
+// Exact search for the most balanced worlds still to be had, knowing the order of the deck.
+// A seeded deal is fixed from the start, so this is perfect play with hindsight: the ceiling
+// a human could have reached, not what they could have known at the time.
+//
+// Worlds with the same polarity and cards of the same value are interchangeable, and Pleroma
+// is Kenoma with every polarity negated, so positions are remembered in that reduced form and
+// only one of each kind of move is tried.
+
+#[derive(Clone, Copy)]
+struct Position{
+    polarity: [i8; WORLDS],
+    hand: [i8; HAND_SIZE],
+    left: usize, // cards still in the deck
+    pleroma: bool,
+}
+
+impl Position{
+    fn of(game: &Game) -> Self{
+        Position{ polarity: game.polarity, hand: *game.hand(), left: game.deck.len(), pleroma: game.pleroma }
+    }
+
+    fn key(&self) -> u128{
+        let mut polarity = self.polarity;
+        if self.pleroma{
+            for pol in polarity.iter_mut(){
+                *pol = -*pol;
+            }
+        }
+        polarity.sort_unstable();
+        let mut hand = self.hand;
+        hand.sort_unstable();
+        let mut key = self.left as u128;
+        for value in polarity.iter().chain(hand.iter()){
+            key = (key << 8) | *value as u8 as u128;
+        }
+        key
+    }
+
+    // Same as Game::apply, minus the bookkeeping. Returns the worlds scored.
+    fn play(&mut self, mv: Move, deck: &[i8], clamp: i8) -> u16{
+        let slot = mv.slot() as usize;
+        let card = self.hand[slot];
+        let mut scored = 0;
+        match mv{
+            Move::Push{ world, .. } => {
+                let pol = &mut self.polarity[world as usize];
+                *pol = if self.pleroma { pol.saturating_add(card) } else { pol.saturating_sub(card) }.clamp(-clamp, clamp);
+            }
+            Move::Claim{ .. } => {
+                for pol in self.polarity.iter_mut(){
+                    if *pol == 0{
+                        *pol = if self.pleroma { card } else { -card }.clamp(-clamp, clamp);
+                        scored += 1;
+                    }
+                }
+                self.pleroma = !self.pleroma;
+            }
+        }
+        self.left -= 1;
+        self.hand[slot] = deck[self.left];
+        scored
+    }
+
+    // One move per kind of card and kind of target.
+    fn moves(&self) -> Vec<Move>{
+        let mut moves = Vec::with_capacity(HAND_SIZE * (WORLDS + 1));
+        for slot in 0..HAND_SIZE{
+            if self.hand[..slot].contains(&self.hand[slot]){
+                continue;
+            }
+            for world in 0..WORLDS{
+                if !self.polarity[..world].contains(&self.polarity[world]){
+                    moves.push(Move::Push{ slot: slot as u8, world: world as u8 });
+                }
+            }
+            moves.push(Move::Claim{ slot: slot as u8 });
+        }
+        moves
+    }
+}
+
+// The keys are already well spread, so a multiply is all the hashing they need.
+#[derive(Default)]
+struct KeyHasher(u64);
+
+impl Hasher for KeyHasher{
+    fn finish(&self) -> u64{
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]){
+        for byte in bytes{
+            self.0 = (self.0.rotate_left(8) ^ *byte as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        }
+    }
+
+    fn write_u128(&mut self, value: u128){
+        self.0 = ((value as u64) ^ ((value >> 64) as u64).wrapping_mul(0xff51_afd7_ed55_8ccd)).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        self.0 ^= self.0 >> 29;
+    }
+}
+
+#[derive(Default)]
+pub struct Solver{
+    deck: Vec<i8>,
+    clamp: i8,
+    memo: HashMap<u128, u16, BuildHasherDefault<KeyHasher>>,
+}
+
+impl Solver{
+    pub fn new() -> Self{
+        Solver::default()
+    }
+
+    fn prepare(&mut self, game: &Game){
+        // Cards are drawn from the end, so a later turn of the same deal has a prefix of its deck.
+        if !self.deck.starts_with(&game.deck) || self.clamp != game.rules.clamp{
+            self.deck = game.deck.clone();
+            self.clamp = game.rules.clamp;
+            self.memo.clear();
+        }
+    }
+
+    fn search(&mut self, position: &Position) -> u16{
+        if position.left == 0{
+            return 0;
+        }
+        let key = position.key();
+        if let Some(value) = self.memo.get(&key){
+            return *value;
+        }
+        let mut best = 0;
+        for mv in position.moves(){
+            best = best.max(self.after(position, mv));
+        }
+        self.memo.insert(key, best);
+        best
+    }
+
+    fn after(&mut self, position: &Position, mv: Move) -> u16{
+        let mut next = *position;
+        let deck = std::mem::take(&mut self.deck);
+        let scored = next.play(mv, &deck, self.clamp);
+        self.deck = deck;
+        scored + self.search(&next)
+    }
+
+    // Balanced worlds still to be scored from here with perfect play.
+    pub fn value(&mut self, game: &Game) -> u16{
+        self.prepare(game);
+        self.search(&Position::of(game))
+    }
+
+    // Worlds scored by mv itself plus everything perfect play gets afterwards.
+    pub fn move_value(&mut self, game: &Game, mv: Move) -> u16{
+        if game.check(mv).is_err(){
+            return 0;
+        }
+        self.prepare(game);
+        self.after(&Position::of(game), mv)
+    }
+
+    pub fn best_move(&mut self, game: &Game) -> Option<(Move, u16)>{
+        if game.is_over(){
+            return None;
+        }
+        self.prepare(game);
+        let position = Position::of(game);
+        let mut best: Option<(Move, u16)> = None;
+        for mv in position.moves(){
+            let value = self.after(&position, mv);
+            if best.is_none_or(|(_, best_value)| value > best_value){
+                best = Some((mv, value));
+            }
+        }
+        best
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TurnReview{
+    pub played: Move,
+    pub best: Move,
+    pub best_value: u16, // worlds perfect play would still have scored from this turn on
+    pub lost: u16, // how many of those the played move gave up
+}
+
+// Goes over a finished or partial game turn by turn, starting from its deal. Setting cancel
+// stops it after the turn in hand, with the reviews done so far.
+pub fn review(start: &Game, moves: &[Move], cancel: &AtomicBool) -> Vec<TurnReview>{
+    let mut solver = Solver::new();
+    let mut game = start.clone();
+    let mut reviews = Vec::with_capacity(moves.len());
+    for mv in moves{
+        if cancel.load(Ordering::Relaxed){
+            break;
+        }
+        let Some((best, best_value)) = solver.best_move(&game) else { break };
+        let played_value = solver.move_value(&game, *mv);
+        reviews.push(TurnReview{ played: *mv, best, best_value, lost: best_value - played_value });
+        if game.apply(*mv).is_err(){
+            break;
+        }
+    }
+    reviews
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+    use crate::rules::{RulePreset, Rules};
+
+    // Every legal move, every time, with nothing remembered or left out.
+    fn brute_force(game: &Game) -> u16{
+        game.legal_moves().into_iter().map(|mv| {
+            let mut next = game.clone();
+            let scored = next.apply(mv).unwrap().balanced.len() as u16;
+            scored + brute_force(&next)
+        }).max().unwrap_or(0)
+    }
+
+    fn small_game(rng: &mut ChaCha8Rng) -> Game{
+        let rules = RulePreset::Tight.rules();
+        let polarity = [(); WORLDS].map(|_| rng.gen_range(-rules.clamp..=rules.clamp));
+        let hand = [(); HAND_SIZE].map(|_| rng.gen_range(1..=rules.max_card));
+        let deck = (0..rng.gen_range(1..=3)).map(|_| rng.gen_range(1..=rules.max_card)).collect();
+        let mut game = Game::from_parts(rules, polarity, hand, deck);
+        game.pleroma = rng.gen_bool(0.5);
+        game
+    }
+
+    #[test]
+    fn hand_worked_positions(){
+        let rules = RulePreset::Classic.rules();
+        let mut solver = Solver::new();
+        let all_balanced = Game::from_parts(rules.clone(), [0; WORLDS], [1, 2, 3, 4], vec![1]);
+        assert_eq!(solver.value(&all_balanced), 4);
+        assert_eq!(solver.best_move(&all_balanced).map(|(mv, _)| mv), Some(Move::Claim{ slot: 0 }));
+        // Pull the 3 down with the 3, then claim it: one world, and nothing better in two moves.
+        let one_world = Game::from_parts(rules.clone(), [3, -2, 5, 5], [3, 1, 1, 1], vec![2, 2]);
+        assert_eq!(solver.value(&one_world), 1);
+        assert_eq!(solver.move_value(&one_world, Move::Push{ slot: 0, world: 0 }), 1);
+        assert_eq!(solver.move_value(&one_world, Move::Claim{ slot: 0 }), 0);
+        let over = Game::from_parts(rules, [0; WORLDS], [1; HAND_SIZE], Vec::new());
+        assert_eq!((solver.value(&over), solver.best_move(&over)), (0, None));
+    }
+
+    #[test]
+    fn matches_brute_force_on_small_positions(){
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        for _ in 0..40{
+            let game = small_game(&mut rng);
+            let expected = brute_force(&game);
+            assert_eq!(Solver::new().value(&game), expected, "{game:?}");
+            let (mv, value) = Solver::new().best_move(&game).unwrap();
+            let mut next = game.clone();
+            let scored = next.apply(mv).unwrap().balanced.len() as u16;
+            assert_eq!((value, scored + brute_force(&next)), (expected, expected), "{game:?}");
+        }
+    }
+
+    #[test]
+    fn losses_add_up_to_the_gap_from_perfect_play(){
+        let start = Game::new(Rules{ deck_size: 8, ..RulePreset::Classic.rules() }, 3);
+        let mut game = start.clone();
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let mut moves = Vec::new();
+        while !game.is_over(){
+            let legal = game.legal_moves();
+            let mv = legal[rng.gen_range(0..legal.len())];
+            game.apply(mv).unwrap();
+            moves.push(mv);
+        }
+        let reviews = review(&start, &moves, &AtomicBool::new(false));
+        assert_eq!(reviews.len(), moves.len());
+        let lost: u16 = reviews.iter().map(|review| review.lost).sum();
+        assert_eq!(lost, Solver::new().value(&start) - game.score);
+        assert_eq!(reviews[0].best_value, Solver::new().value(&start));
+    }
+
+    #[test]
+    fn cancelled_reviews_stop_early(){
+        let start = Game::new(RulePreset::Quick.rules(), 3);
+        let moves = vec![Move::Claim{ slot: 0 }; 12];
+        assert!(review(&start, &moves, &AtomicBool::new(true)).is_empty());
+    }
+}
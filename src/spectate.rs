@@ -0,0 +1,201 @@
+use std::{fmt, io::{self, ErrorKind}, net::{TcpListener, TcpStream}, time::Duration};
+use serde::{Deserialize, Serialize};
+use tungstenite::{protocol::WebSocket, Message};
+use crate::{protocol::{move_events, Event, State}, rules::{Game, Move, RulePreset}};
+
+// A running game published over a WebSocket on localhost, for stream overlays, web pages and
+// spectating copies of the game. Every text message is one Frame:
+//
+//   {"frame":"game","state":{...},"moves":[...]}    a new deal, or the game so far when a
+//                                                   spectator connects; moves lead from the
+//                                                   deal to the state
+//   {"frame":"move","mv":{"Push":{"slot":1,"world":2}},"events":[...],"state":{...}}
+//
+// state and events are those of the remote control protocol, see src/protocol.rs.
+
+pub const SPECTATOR_PORT: u16 = 7879;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "frame", rename_all = "snake_case")]
+pub enum Frame{
+    Game{ state: State, moves: Vec<Move> },
+    Move{ mv: Move, events: Vec<Event>, state: State },
+}
+
+#[derive(Debug)]
+pub enum SpectateError{
+    Io(io::Error),
+    WebSocket(Box<tungstenite::Error>),
+    Garbled(String),
+    Closed,
+}
+
+impl fmt::Display for SpectateError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            SpectateError::Io(err) => write!(f, "{err}"),
+            SpectateError::WebSocket(err) => write!(f, "{err}"),
+            SpectateError::Garbled(text) => write!(f, "could not understand the game: {text}"),
+            SpectateError::Closed => write!(f, "the game stopped publishing"),
+        }
+    }
+}
+
+impl std::error::Error for SpectateError{}
+
+impl From<io::Error> for SpectateError{
+    fn from(err: io::Error) -> Self{
+        SpectateError::Io(err)
+    }
+}
+
+impl From<tungstenite::Error> for SpectateError{
+    fn from(err: tungstenite::Error) -> Self{
+        match err{
+            tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => SpectateError::Closed,
+            tungstenite::Error::Io(err) => SpectateError::Io(err),
+            err => SpectateError::WebSocket(Box::new(err)),
+        }
+    }
+}
+
+fn would_block(err: &tungstenite::Error) -> bool{
+    matches!(err, tungstenite::Error::Io(err) if err.kind() == ErrorKind::WouldBlock)
+}
+
+// The publishing side. Follows the game through publish_deal and publish_move, and brings
+// every spectator that connects up to date.
+pub struct Broadcaster{
+    listener: TcpListener,
+    spectators: Vec<WebSocket<TcpStream>>,
+    preset: Option<RulePreset>,
+    seed: u32,
+    game: Option<Game>,
+    moves: Vec<Move>,
+}
+
+impl Broadcaster{
+    pub fn listen(port: u16) -> Result<Self, SpectateError>{
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Broadcaster{ listener, spectators: Vec::new(), preset: None, seed: 0, game: None, moves: Vec::new() })
+    }
+
+    pub fn spectators(&self) -> usize{
+        self.spectators.len()
+    }
+
+    fn game_frame(&self) -> Option<Frame>{
+        let game = self.game.as_ref()?;
+        Some(Frame::Game{ state: State::new(game, self.preset, self.seed), moves: self.moves.clone() })
+    }
+
+    // Takes in whoever connected since the last call, and drops spectators who left.
+    pub fn poll(&mut self){
+        while let Ok((stream, _)) = self.listener.accept(){
+            let _ = stream.set_nodelay(true);
+            let _ = stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)); // a page on localhost answers at once
+            let Ok(mut socket) = tungstenite::accept(stream) else { continue };
+            if socket.get_ref().set_nonblocking(true).is_err(){
+                continue;
+            }
+            if let Some(frame) = self.game_frame(){
+                if !send(&mut socket, &frame){
+                    continue;
+                }
+            }
+            self.spectators.push(socket);
+        }
+        self.spectators.retain_mut(|socket| {
+            loop{ // spectators only read, but close frames and pings still need answering
+                match socket.read(){
+                    Ok(_) => continue,
+                    Err(err) if would_block(&err) => break match socket.flush(){
+                        Ok(()) => true,
+                        Err(err) => would_block(&err),
+                    },
+                    Err(_) => break false,
+                }
+            }
+        });
+    }
+
+    pub fn publish_deal(&mut self, game: &Game, preset: Option<RulePreset>, seed: u32){
+        self.preset = preset;
+        self.seed = seed;
+        self.game = Some(game.clone());
+        self.moves.clear();
+        if let Some(frame) = self.game_frame(){
+            self.broadcast(&frame);
+        }
+    }
+
+    pub fn publish_move(&mut self, mv: Move){
+        let Some(game) = self.game.as_mut() else { return };
+        let pleroma = game.pleroma;
+        let Ok(outcome) = game.apply(mv) else { return };
+        let frame = Frame::Move{ mv, events: move_events(mv, pleroma, &outcome, game), state: State::new(game, self.preset, self.seed) };
+        self.moves.push(mv);
+        self.broadcast(&frame);
+    }
+
+    // The moves published since the last deal.
+    pub fn published(&self) -> usize{
+        self.moves.len()
+    }
+
+    fn broadcast(&mut self, frame: &Frame){
+        self.spectators.retain_mut(|socket| send(socket, frame));
+    }
+}
+
+impl Drop for Broadcaster{
+    fn drop(&mut self){
+        for socket in self.spectators.iter_mut(){
+            let _ = socket.close(None);
+            let _ = socket.flush();
+        }
+    }
+}
+
+fn send(socket: &mut WebSocket<TcpStream>, frame: &Frame) -> bool{
+    let text = serde_json::to_string(frame).expect("frames always serialize");
+    match socket.send(Message::Text(text)){
+        Ok(()) => true,
+        Err(err) => would_block(&err), // queued, it goes out with the next flush
+    }
+}
+
+// The watching side.
+pub struct Spectator{
+    socket: WebSocket<TcpStream>,
+}
+
+impl Spectator{
+    // `address` is host:port, or a host alone for SPECTATOR_PORT.
+    pub fn connect(address: &str) -> Result<Self, SpectateError>{
+        let address = if address.contains(':') { address.to_owned() } else { format!("{address}:{SPECTATOR_PORT}") };
+        let stream = TcpStream::connect(&address)?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let (socket, _) = tungstenite::client(format!("ws://{address}/"), stream).map_err(|err| match err{
+            tungstenite::HandshakeError::Failure(err) => SpectateError::from(err),
+            tungstenite::HandshakeError::Interrupted(_) => SpectateError::Io(ErrorKind::TimedOut.into()),
+        })?;
+        socket.get_ref().set_nonblocking(true)?;
+        Ok(Spectator{ socket })
+    }
+
+    // The next frame, if one has arrived.
+    pub fn poll(&mut self) -> Result<Option<Frame>, SpectateError>{
+        loop{
+            match self.socket.read(){
+                Ok(Message::Text(text)) => return serde_json::from_str(&text).map(Some).map_err(|_| SpectateError::Garbled(text)),
+                Ok(Message::Close(_)) => return Err(SpectateError::Closed),
+                Ok(_) => continue,
+                Err(err) if would_block(&err) => return Ok(None),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt::Write;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use pleromakenoma::rules::{Move, HAND_SIZE, WORLDS};
+use crate::{session::Turn, storage};
+
+const STATS_FILE: &str = "stats.json";
+const EXPORT_JSON: &str = "stats-export.json";
+const EXPORT_CSV: &str = "stats-export.csv";
+
+// Totals over every finished game of a profile, see storage.rs. Abandoned games are not counted.
+#[derive(Resource, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LifetimeStats{
+    #[serde(skip)]
+    pub profile: String, // the one these belong to
+    pub games_played: u32,
+    pub total_score: u32,
+    pub best_score: u16,
+    pub total_turns: u32,
+    pub slot_uses: [u32; HAND_SIZE],
+    pub world_balances: [u32; WORLDS],
+    pub kenoma_balances: u32,
+    pub pleroma_balances: u32,
+    pub clamps: u32, // pushes cut short at the edge of the board
+}
+
+impl LifetimeStats{
+    pub fn load(profile: &str) -> Self{
+        let stats: LifetimeStats = storage::load_profile(profile, STATS_FILE).unwrap_or_default();
+        LifetimeStats{ profile: profile.to_owned(), ..stats }
+    }
+
+    pub fn save(&self){
+        storage::save(&storage::profile_file(&self.profile, STATS_FILE), self);
+    }
+
+    pub fn record(&mut self, history: &[Turn], score: u16){
+        self.games_played += 1;
+        self.total_score += score as u32;
+        self.best_score = self.best_score.max(score);
+        self.total_turns += history.len() as u32;
+        for turn in history{
+            self.slot_uses[turn.mv.slot() as usize] += 1;
+            self.clamps += turn.outcome.clamped as u32;
+            if let Move::Claim{ .. } = turn.mv{
+                for world in turn.outcome.balanced.iter(){
+                    self.world_balances[*world as usize] += 1;
+                }
+                let balanced = turn.outcome.balanced.len() as u32;
+                if turn.pleroma { self.pleroma_balances += balanced } else { self.kenoma_balances += balanced }
+            }
+        }
+        self.save();
+    }
+
+    pub fn average_score(&self) -> f32{
+        self.total_score as f32 / self.games_played.max(1) as f32
+    }
+
+    pub fn most_used_slot(&self) -> Option<u8>{
+        let (slot, uses) = self.slot_uses.iter().enumerate().max_by_key(|(slot, uses)| (**uses, std::cmp::Reverse(*slot)))?;
+        if *uses == 0 { None } else { Some(slot as u8) }
+    }
+
+    pub fn turns_per_balance(&self) -> Option<f32>{
+        let balances = self.kenoma_balances + self.pleroma_balances;
+        if balances == 0 { None } else { Some(self.total_turns as f32 / balances as f32) }
+    }
+
+    // (name, value) pairs in the order the stats screen and the exports list them.
+    pub fn rows(&self) -> Vec<(String, String)>{
+        let mut rows = vec![
+            ("games_played".to_owned(), self.games_played.to_string()),
+            ("average_score".to_owned(), format!("{:.2}", self.average_score())),
+            ("best_score".to_owned(), self.best_score.to_string()),
+            ("most_used_slot".to_owned(), self.most_used_slot().map(|slot| (slot + 1).to_string()).unwrap_or_default()),
+        ];
+        for (world, count) in self.world_balances.iter().enumerate(){
+            rows.push((format!("world_{}_balances", world + 1), count.to_string()));
+        }
+        rows.push(("kenoma_balances".to_owned(), self.kenoma_balances.to_string()));
+        rows.push(("pleroma_balances".to_owned(), self.pleroma_balances.to_string()));
+        rows.push(("clamps".to_owned(), self.clamps.to_string()));
+        rows.push(("turns_per_balance".to_owned(), self.turns_per_balance().map(|turns| format!("{turns:.2}")).unwrap_or_default()));
+        rows
+    }
+
+    // Writes both exports next to the profile's save files and returns where they went.
+    pub fn export(&self) -> Option<String>{
+        let mut csv = "stat,value\n".to_owned();
+        for (name, value) in self.rows(){
+            writeln!(csv, "{name},{value}").expect("writing to a String cannot fail");
+        }
+        let json: serde_json::Map<String, serde_json::Value> = self.rows().into_iter()
+            .map(|(name, value)| (name, value.parse::<f64>().map_or(serde_json::Value::Null, serde_json::Value::from)))
+            .collect();
+        let csv_file = storage::profile_file(&self.profile, EXPORT_CSV);
+        storage::save(&storage::profile_file(&self.profile, EXPORT_JSON), &json);
+        storage::save_text(&csv_file, &csv);
+        let dir = storage::data_path(&csv_file)?;
+        Some(dir.parent()?.display().to_string())
+    }
+}
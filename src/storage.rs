@@ -0,0 +1,95 @@
+use std::{fs, path::PathBuf};
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+// Everything kept between sessions is a JSON file in the user's data directory.
+// The web build has no file system, so there it simply starts fresh every time.
+//
+// Statistics and achievements belong to a profile and live in a directory of its own under
+// profiles/, so that everyone sharing a machine keeps their own. Settings and bindings are shared.
+
+pub const DEFAULT_PROFILE: &str = "default";
+const PROFILES_DIR: &str = "profiles";
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn data_path(file: &str) -> Option<PathBuf>{
+    dirs::data_dir().map(|dir| dir.join("pleromakenoma").join(file))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn data_path(_file: &str) -> Option<PathBuf>{
+    None
+}
+
+pub fn load<T: DeserializeOwned>(file: &str) -> Option<T>{
+    let text = fs::read_to_string(data_path(file)?).ok()?;
+    match serde_json::from_str(&text){
+        Ok(value) => Some(value),
+        Err(err) => {
+            warn!("ignoring {file}: {err}");
+            None
+        }
+    }
+}
+
+pub fn is_profile_name(name: &str) -> bool{
+    !name.is_empty() && name.len() <= 24 && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+pub fn profile_file(profile: &str, file: &str) -> String{
+    format!("{PROFILES_DIR}/{profile}/{file}")
+}
+
+// Data saved before there were profiles belongs to the default one.
+pub fn load_profile<T: DeserializeOwned>(profile: &str, file: &str) -> Option<T>{
+    load(&profile_file(profile, file)).or_else(|| if profile == DEFAULT_PROFILE { load(file) } else { None })
+}
+
+// Every profile that has saved something, plus the default and the current one, by name.
+pub fn profiles(current: &str) -> Vec<String>{
+    let dir = data_path(PROFILES_DIR).and_then(|dir| fs::read_dir(dir).ok());
+    let mut profiles: Vec<String> = dir.into_iter().flatten()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| is_profile_name(name))
+        .collect();
+    for name in [DEFAULT_PROFILE, current]{
+        if !profiles.iter().any(|profile| profile == name){
+            profiles.push(name.to_owned());
+        }
+    }
+    profiles.sort();
+    profiles
+}
+
+pub fn save<T: Serialize>(file: &str, value: &T){
+    let text = serde_json::to_string_pretty(value).expect("save data is always serializable");
+    save_text(file, &text);
+}
+
+pub fn save_text(file: &str, text: &str){
+    let Some(path) = data_path(file) else { return };
+    if let Some(dir) = path.parent(){
+        if let Err(err) = fs::create_dir_all(dir){
+            warn!("could not create {}: {err}", dir.display());
+            return;
+        }
+    }
+    if let Err(err) = fs::write(&path, text){
+        warn!("could not write {}: {err}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn profile_names_stay_inside_the_profiles_directory(){
+        assert!(is_profile_name("default"));
+        assert!(is_profile_name("Ada_2-b"));
+        for name in ["", "..", "a/b", "a\\b", "x y", "ünï", &"a".repeat(25)]{
+            assert!(!is_profile_name(name), "{name:?}");
+        }
+        assert_eq!(profile_file("ada", "stats.json"), "profiles/ada/stats.json");
+    }
+}
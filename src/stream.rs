@@ -0,0 +1,58 @@
+use bevy::prelude::*;
+use pleromakenoma::{rules::Game, spectate::{Broadcaster, SPECTATOR_PORT}};
+use crate::{session::Session, GameSettings};
+
+// The spectator stream: with the setting on, the game on the board is published on
+// ws://127.0.0.1:7879 for overlays, web pages and other copies of the game to watch,
+// see pleromakenoma::spectate.
+#[derive(Resource, Default)]
+pub struct Stream{
+    broadcaster: Option<Broadcaster>,
+    failed: bool, // the port is taken, tried again once the setting is switched off and on
+    dealt: bool, // there is a game on the board to show
+    published: bool, // the spectators have the current deal
+}
+
+// A new deal goes out on the next publish_game.
+pub fn note_deal(mut stream: ResMut<Stream>){
+    stream.dealt = true;
+    stream.published = false;
+}
+
+pub fn publish_game(settings: Res<GameSettings>, session: Res<Session>, mut stream: ResMut<Stream>){
+    let stream = &mut *stream;
+    if !settings.spectator_stream{
+        stream.broadcaster = None;
+        stream.failed = false;
+        return;
+    }
+    if stream.broadcaster.is_none() && !stream.failed{
+        match Broadcaster::listen(SPECTATOR_PORT){
+            Ok(broadcaster) => {
+                info!("publishing the game on ws://127.0.0.1:{SPECTATOR_PORT}");
+                stream.broadcaster = Some(broadcaster);
+                stream.published = false;
+            }
+            Err(err) => {
+                warn!("cannot publish the game: {err}");
+                stream.failed = true;
+            }
+        }
+    }
+    let Some(broadcaster) = stream.broadcaster.as_mut() else { return };
+    broadcaster.poll();
+    if !stream.dealt{
+        return;
+    }
+    if !stream.published{ // dealt again from the seed, so the moves so far can follow
+        let (start, preset) = match &session.puzzle{
+            Some(puzzle) => (puzzle.game(), None), // no preset deals it, spectators get the position alone
+            None => (Game::deal(session.preset.rules(), session.seed, session.game.mode), Some(session.preset)),
+        };
+        broadcaster.publish_deal(&start, preset, session.seed);
+        stream.published = true;
+    }
+    for turn in session.history.iter().skip(broadcaster.published()){
+        broadcaster.publish_move(turn.mv);
+    }
+}
@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use pleromakenoma::rules::Mode;
+use crate::{session::Session, BalancedWorlds, Board};
+
+const ACTIVE_COLOR: Color = Color::WHITE;
+const WAITING_COLOR: Color = Color::rgb(0.45, 0.45, 0.45);
+
+#[derive(Component)]
+pub struct TurnIndicator{}
+
+#[derive(Component)]
+pub struct PlayerLabel{
+    player: u8,
+}
+
+pub fn player_name(mode: Mode, player: u8) -> String{
+    match (mode, player){
+        (Mode::Dimensions, 0) => "Kenoma".to_owned(),
+        (Mode::Dimensions, _) => "Pleroma".to_owned(),
+        _ => format!("Player {}", player + 1),
+    }
+}
+
+// Whose turn it is and what each player has claimed, at the top of the window.
+pub fn spawn_versus_panel(mut commands: Commands, asset_server: Res<AssetServer>, session: Res<Session>){
+    if !session.game.mode.is_versus(){
+        return;
+    }
+    let mode = session.game.mode;
+    let font = asset_server.load("Play-Regular.ttf");
+    let style = |font_size: f32| TextStyle{ font: font.clone(), font_size, color: WAITING_COLOR };
+    commands.spawn((NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.),
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        z_index: ZIndex::Global(-1), // under any menu opened over the board
+        ..default()
+    },
+    Board{},
+    )).with_children(|parent| {
+        parent.spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(6.)),
+                ..default()
+            },
+            background_color: Color::rgba(0.1, 0.1, 0.1, 0.7).into(),
+            ..default()
+        }).with_children(|panel| {
+            panel.spawn((TextBundle::from_section("", TextStyle{ color: ACTIVE_COLOR, ..style(18.) }), TurnIndicator{}));
+            panel.spawn(NodeBundle {
+                style: Style {
+                    column_gap: Val::Px(24.),
+                    ..default()
+                },
+                ..default()
+            }).with_children(|row| {
+                for player in 0..2{
+                    row.spawn((TextBundle::from_section(player_name(mode, player), style(14.)), PlayerLabel{ player }));
+                    row.spawn((TextBundle::from_section("0", style(14.)), PlayerLabel{ player }, BalancedWorlds{ capacity: 0, player: Some(player) }));
+                }
+            });
+        });
+    });
+}
+
+pub fn update_turn_indicator(
+    session: Res<Session>,
+    mut query_indicator: Query<&mut Text, (With<TurnIndicator>, Without<PlayerLabel>)>,
+    mut query_labels: Query<(&mut Text, &PlayerLabel)>,
+){
+    if !session.is_changed() || !session.game.mode.is_versus(){
+        return;
+    }
+    let game = &session.game;
+    for mut text in query_indicator.iter_mut(){
+        text.sections[0].value = if game.is_over(){
+            "Game over".to_owned()
+        } else {
+            format!("{} to play", player_name(game.mode, game.player))
+        };
+    }
+    for (mut text, label) in query_labels.iter_mut(){
+        text.sections[0].style.color = if label.player == game.player && !game.is_over() { ACTIVE_COLOR } else { WAITING_COLOR };
+    }
+}